@@ -0,0 +1,2 @@
+alter table idempotency_key
+    add column response_location text;
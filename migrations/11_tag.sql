@@ -0,0 +1,28 @@
+create table tag
+(
+    tag_id     bigserial primary key,
+    name       text unique not null,
+    created_at timestamptz not null default now()
+);
+
+create table article_tag
+(
+    article_id uuid   not null references article (article_id) on delete cascade,
+    tag_id     bigint not null references tag (tag_id) on delete cascade,
+    primary key (article_id, tag_id)
+);
+
+create index on article_tag (tag_id);
+
+insert into tag (name)
+select distinct name
+from article, unnest(article.tag_list) tags(name);
+
+insert into article_tag (article_id, tag_id)
+select distinct article.article_id, tag.tag_id
+from article, unnest(article.tag_list) tags(name)
+inner join tag using (name);
+
+drop index article_tags_gin;
+
+alter table article drop column tag_list;
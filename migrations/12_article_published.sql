@@ -0,0 +1 @@
+alter table article add column published boolean not null default true;
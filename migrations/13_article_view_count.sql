@@ -0,0 +1,11 @@
+alter table article add column view_count bigint not null default 0;
+
+-- Counting a view is not an edit, so it must not bump `updated_at`.
+drop trigger set_updated_at on article;
+
+create trigger set_updated_at
+    before update
+    on article
+    for each row
+    when ((to_jsonb(OLD) - 'view_count') is distinct from (to_jsonb(NEW) - 'view_count'))
+execute function set_updated_at();
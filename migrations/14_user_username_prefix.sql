@@ -0,0 +1,3 @@
+-- Backs username autocomplete. `username` uses a nondeterministic collation, which LIKE does not
+-- support, so prefixes are matched against the lowercased name under "C" instead.
+create index user_username_prefix on "user" ((lower(username) collate "C"));
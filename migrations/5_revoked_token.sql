@@ -0,0 +1,8 @@
+create table revoked_token
+(
+    jti        uuid primary key,
+    expires_at timestamptz not null,
+    created_at timestamptz not null default now()
+);
+
+create index revoked_token_expires_at_key on revoked_token (expires_at);
@@ -0,0 +1,3 @@
+create index article_search_gin on article using gin (
+    to_tsvector('english', title || ' ' || description || ' ' || body)
+);
@@ -0,0 +1,9 @@
+create table password_reset
+(
+    token_hash bytea primary key,
+    user_id    uuid        not null references "user" (user_id) on delete cascade,
+    expires_at timestamptz not null,
+    created_at timestamptz not null default now()
+);
+
+create index password_reset_user_id_key on password_reset (user_id);
@@ -0,0 +1,11 @@
+create table idempotency_key
+(
+    user_id       uuid        not null references "user" (user_id) on delete cascade,
+    key           text        not null,
+    request_path  text        not null,
+    -- Both null while the first request is still being handled.
+    status_code   int2,
+    response_body bytea,
+    created_at    timestamptz not null default now(),
+    primary key (user_id, key)
+);
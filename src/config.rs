@@ -1,20 +1,197 @@
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
 /// Real world application
 #[derive(Parser, Debug)]
 pub struct Config {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Address the HTTP server listens on
+    #[arg(long, env, default_value = "0.0.0.0:8080")]
+    pub listen_addr: SocketAddr,
+    /// PEM certificate chain; together with the key, the server terminates TLS itself
+    #[arg(long, env, requires = "tls_key_path")]
+    pub tls_cert_path: Option<PathBuf>,
+    /// PEM private key for `tls_cert_path`
+    #[arg(long, env, requires = "tls_cert_path")]
+    pub tls_key_path: Option<PathBuf>,
     /// Database URL
     #[arg(long, env)]
     pub database_url: String,
-    /// RSA Private Key
+    /// Seconds a single SQL statement may run before Postgres cancels it
+    #[arg(long, env, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub database_statement_timeout_seconds: u64,
+    /// Algorithm used to sign session tokens
+    #[arg(long, env, value_enum, default_value_t = JwtAlgorithm::Rs256)]
+    pub jwt_algorithm: JwtAlgorithm,
+    /// RSA Private Key, required for RS256
     #[arg(long, env, value_parser = load_key)]
-    pub rsa_private_key: String,
-    /// RSA Public Key
+    pub rsa_private_key: Option<String>,
+    /// RSA Public Key, required for RS256
     #[arg(long, env, value_parser = load_key)]
-    pub rsa_public_key: String,
+    pub rsa_public_key: Option<String>,
+    /// Public keys of retired RSA key pairs, whose tokens are still accepted
+    #[arg(long, env, value_delimiter = ',', value_parser = load_key)]
+    pub rsa_previous_public_keys: Vec<String>,
+    /// Shared secret, required for HS256
+    #[arg(long, env, hide_env_values = true)]
+    pub jwt_secret: Option<String>,
+    /// Lifetime of issued session tokens in seconds
+    #[arg(long, env, default_value_t = 1_209_600, value_parser = clap::value_parser!(i64).range(1..))]
+    pub session_length_seconds: i64,
+    /// Clock skew in seconds tolerated when checking token expiry
+    #[arg(long, env, default_value_t = 60)]
+    pub jwt_leeway_seconds: u64,
+    /// `iss` claim put in issued tokens and required on incoming ones
+    #[arg(long, env, default_value = "conduit")]
+    pub jwt_issuer: String,
+    /// `aud` claim put in issued tokens and required on incoming ones
+    #[arg(long, env, default_value = "conduit")]
+    pub jwt_audience: String,
+    /// Argon2 memory cost for new password hashes, in KiB
+    #[arg(long, env, default_value_t = argon2::Params::DEFAULT_M_COST)]
+    pub argon2_memory_cost_kib: u32,
+    /// Argon2 number of passes for new password hashes
+    #[arg(long, env, default_value_t = argon2::Params::DEFAULT_T_COST)]
+    pub argon2_time_cost: u32,
+    /// Argon2 degree of parallelism for new password hashes
+    #[arg(long, env, default_value_t = argon2::Params::DEFAULT_P_COST)]
+    pub argon2_parallelism: u32,
+    /// Lifetime of password reset tokens in seconds
+    #[arg(long, env, default_value_t = 3600, value_parser = clap::value_parser!(u32).range(1..))]
+    pub password_reset_token_seconds: u32,
+    /// Write password reset tokens to the server log. For development only: anyone who can read
+    /// the logs can then reset any account's password
+    #[arg(long, env)]
+    pub log_password_reset_tokens: bool,
+    /// Origins allowed to make cross-origin requests, comma separated
+    #[arg(long, env, value_delimiter = ',')]
+    pub cors_allowed_origins: Vec<String>,
+    /// Hosts profile images may be served from, comma separated; any host when empty
+    #[arg(long, env, value_delimiter = ',')]
+    pub image_allowed_hosts: Vec<String>,
+    /// Give an article a new slug when its title changes, unless the update says otherwise
+    #[arg(long, env)]
+    pub regenerate_slug_on_title_change: bool,
+    /// Minimum number of seconds a user must wait between creating two articles; admins are exempt
+    #[arg(long, env)]
+    pub article_creation_cooldown_seconds: Option<u64>,
+    /// Page size of paginated endpoints when the request has no `limit`
+    #[arg(long, env, default_value_t = 20, value_parser = clap::value_parser!(i64).range(1..))]
+    pub default_page_size: i64,
+    /// Largest `limit` honoured by paginated endpoints; bigger ones are cut down to it
+    #[arg(long, env, default_value_t = 100, value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_page_size: i64,
+    /// Most entries per list in a user's data export
+    #[arg(long, env, default_value_t = 10_000, value_parser = clap::value_parser!(i64).range(1..))]
+    pub export_max_items: i64,
+    /// Most users one account may follow
+    #[arg(long, env, default_value_t = 5_000, value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_follows_per_user: i64,
+    /// Most distinct tags an article may have
+    #[arg(long, env, default_value_t = 10)]
+    pub max_tags_per_article: usize,
+    /// Longest article body accepted, in characters
+    #[arg(long, env, default_value_t = 100_000)]
+    pub max_article_body_length: usize,
+    /// Reading speed used for the reading time estimate of articles
+    #[arg(long, env, default_value_t = 200, value_parser = clap::value_parser!(u32).range(1..))]
+    pub reading_words_per_minute: u32,
+    /// Longest tag accepted, in characters
+    #[arg(long, env, default_value_t = 30)]
+    pub max_tag_length: usize,
+    /// Seconds for which a repeated `Idempotency-Key` replays the first response
+    #[arg(long, env, default_value_t = 86_400, value_parser = clap::value_parser!(u32).range(1..))]
+    pub idempotency_key_ttl_seconds: u32,
+    /// Number of tags included in the articles by tag listing
+    #[arg(long, env, default_value_t = 5)]
+    pub articles_by_tag_tag_count: i64,
+    /// Number of articles included per tag in the articles by tag listing
+    #[arg(long, env, default_value_t = 5)]
+    pub articles_by_tag_article_count: i64,
+    /// Number of login attempts allowed per client address within the rate limit window
+    #[arg(long, env, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub login_rate_limit_attempts: u32,
+    /// Length of the login rate limit window in seconds
+    #[arg(long, env, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    pub login_rate_limit_window_seconds: u64,
+    /// Serve `/metrics` on this address instead of alongside the API
+    #[arg(long, env)]
+    pub metrics_listen_addr: Option<SocketAddr>,
+    /// Seconds to wait for in-flight requests to finish after SIGINT or SIGTERM
+    #[arg(long, env, default_value_t = 30)]
+    pub shutdown_timeout_seconds: u64,
+    /// Largest accepted request body in bytes
+    #[arg(long, env, default_value_t = 2 * 1024 * 1024)]
+    pub request_body_limit_bytes: usize,
+    /// Seconds a request may take before it is answered with 408
+    #[arg(long, env, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub request_timeout_seconds: u64,
+    /// Format of log lines written to stdout
+    #[arg(long, env, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+}
+
+impl Config {
+    /// Number of items to return for a requested `limit`, between 1 and `max_page_size`.
+    pub fn page_size(&self, limit: Option<i64>) -> i64 {
+        limit
+            .unwrap_or(self.default_page_size)
+            .clamp(1, self.max_page_size)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Insert demo users, articles, comments, follows and favorites, then exit
+    Seed,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    #[value(name = "RS256")]
+    Rs256,
+    #[value(name = "HS256")]
+    Hs256,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
 }
 
 fn load_key(value: &str) -> std::io::Result<String> {
     fs::read_to_string(value)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(listen_addr: &str) -> Result<Config, clap::Error> {
+        Config::try_parse_from([
+            "conduit",
+            "--database-url",
+            "postgres://localhost/conduit",
+            "--listen-addr",
+            listen_addr,
+        ])
+    }
+
+    #[test]
+    fn listen_addr_accepts_a_socket_address() {
+        let config = parse("127.0.0.1:9000").unwrap();
+
+        assert_eq!(config.listen_addr, "127.0.0.1:9000".parse().unwrap());
+    }
+
+    #[test]
+    fn listen_addr_rejects_anything_else() {
+        assert!(parse("not-an-addr").is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use super::{auth, AppState, Error, Result};
+use crate::http::extractor::Path;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::delete;
+use axum::{middleware, Router};
+use utoipa::OpenApi;
+
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/articles/{slug}", delete(delete_article))
+        .route(
+            "/api/admin/articles/{slug}/comments/{comment_id}",
+            delete(delete_comment),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin,
+        ))
+        .route_layer(middleware::from_fn_with_state(state, auth::auth))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(delete_article, delete_comment))]
+pub(super) struct AdminApi;
+
+/// Deletes the article regardless of who wrote it.
+#[utoipa::path(
+    delete,
+    path = "/api/admin/articles/{slug}",
+    tag = "admin",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    responses(
+        (status = 204, description = "Article deleted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn delete_article(state: State<AppState>, Path(slug): Path<String>) -> Result<StatusCode> {
+    sqlx::query!(
+        // language=PostgreSQL
+        "delete from article where slug = $1 returning 1 \"deleted\"",
+        slug
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Deletes the comment regardless of who wrote it.
+#[utoipa::path(
+    delete,
+    path = "/api/admin/articles/{slug}/comments/{comment_id}",
+    tag = "admin",
+    security(("token" = [])),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+        ("comment_id" = i64, Path, description = "Comment id")
+    ),
+    responses(
+        (status = 204, description = "Comment deleted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not an admin"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn delete_comment(
+    state: State<AppState>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+) -> Result<StatusCode> {
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            delete from article_comment
+            where
+                comment_id = $1
+                and article_id = (select article_id from article where slug = $2)
+            returning 1 "deleted"
+        "#,
+        comment_id,
+        slug
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,16 +1,22 @@
 use super::{auth, Error, Profile, Result};
-use crate::config::Config;
 use crate::http::auth::Claims;
+use crate::http::errors::ResultExt;
+use crate::http::extractor::{Json, Path, Query};
+use crate::http::idempotency;
+use crate::http::transaction::{self, Tx};
 use crate::http::AppState;
-use axum::extract::{Path, State};
-use axum::routing::{delete, get, post};
-use axum::{middleware, Extension, Json, Router};
+use axum::extract::State;
+use axum::http::header::LOCATION;
+use axum::http::{HeaderName, StatusCode};
+use axum::routing::{get, post, put};
+use axum::{middleware, Extension, Router};
 use futures::TryStreamExt;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use serde::de::{self, IntoDeserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use time::OffsetDateTime;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
-pub fn router(state: Arc<Config>) -> Router<AppState> {
+pub fn router(state: AppState) -> Router<AppState> {
     Router::new()
         .route(
             "/api/articles/{slug}/comments",
@@ -22,30 +28,84 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
         .route(
             "/api/articles/{slug}/comments",
             post(add_comment)
+                .route_layer(middleware::from_fn(transaction::transaction))
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    idempotency::idempotency,
+                ))
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
         )
         .route(
             "/api/articles/{slug}/comments/{comment_id}",
-            delete(delete_comment).route_layer(middleware::from_fn_with_state(state, auth::auth)),
+            put(update_comment)
+                .delete(delete_comment)
+                .route_layer(middleware::from_fn_with_state(state, auth::auth)),
         )
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(OpenApi)]
+#[openapi(paths(get_article_comments, add_comment, update_comment, delete_comment))]
+pub(crate) struct CommentsApi;
+
+#[derive(Deserialize, Default, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+struct CommentsQuery {
+    limit: Option<i64>,
+    #[param(value_type = Option<String>)]
+    cursor: Option<CommentCursor>,
+}
+
+/// Where the previous page ended: the `createdAt` of its last comment, a comma and that comment's
+/// id. Made of values rather than a reference to the comment, so it still works once that comment
+/// is deleted.
+struct CommentCursor {
+    created_at: OffsetDateTime,
+    comment_id: i64,
+}
+
+impl<'de> Deserialize<'de> for CommentCursor {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let (created_at, comment_id) = value
+            .rsplit_once(',')
+            .ok_or_else(|| de::Error::custom("cursor must be createdAt,commentId"))?;
+        let created_at = OffsetDateTime::deserialize(
+            IntoDeserializer::<D::Error>::into_deserializer(created_at),
+        )?;
+        let comment_id = comment_id.parse().map_err(de::Error::custom)?;
+
+        Ok(Self {
+            created_at,
+            comment_id,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
 struct CommentBody<T = Comment> {
     comment: T,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct MultipleCommentsBody {
     comments: Vec<Comment>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AddComment {
     body: String,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, ToSchema)]
+struct UpdateComment {
+    body: String,
+}
+
+#[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct Comment {
     id: i64,
@@ -78,20 +138,43 @@ impl CommentFromQuery {
                 bio: self.author_bio,
                 image: self.author_image,
                 following: self.following_author,
+                followers_count: None,
+                following_count: None,
+                created_at: None,
             },
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/articles/{slug}/comments",
+    tag = "comments",
+    security((), ("token" = [])),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+        CommentsQuery
+    ),
+    responses(
+        (status = 200, description = "Comments, oldest first", body = MultipleCommentsBody),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn get_article_comments(
     state: State<AppState>,
     Extension(maybe_claims): Extension<Option<Claims>>,
     Path(slug): Path<String>,
+    Query(query): Query<CommentsQuery>,
 ) -> Result<Json<MultipleCommentsBody>> {
-    let article_id = sqlx::query_scalar!("select article_id from article where slug = $1", slug)
-        .fetch_optional(&state.db)
-        .await?
-        .ok_or(Error::NotFound)?;
+    let article_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select article_id from article where slug = $1 and (published or user_id = $2)"#,
+        slug,
+        maybe_claims.as_ref().map(|claims| claims.sub)
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
 
     let comments = sqlx::query_as!(
         CommentFromQuery,
@@ -109,10 +192,17 @@ async fn get_article_comments(
             from article_comment comment
             inner join "user" author using (user_id)
             where article_id = $2
-            order by created_at
+            and (
+                $3::timestamptz is null or (comment.created_at, comment_id) > ($3, $4)
+            )
+            order by comment.created_at, comment_id
+            limit $5
         "#,
         maybe_claims.as_ref().map(|claims| claims.sub),
-        article_id
+        article_id,
+        query.cursor.as_ref().map(|cursor| cursor.created_at),
+        query.cursor.as_ref().map(|cursor| cursor.comment_id),
+        state.config.page_size(query.limit)
     )
     .fetch(&state.db)
     .map_ok(CommentFromQuery::into_comment)
@@ -122,21 +212,111 @@ async fn get_article_comments(
     Ok(Json(MultipleCommentsBody { comments }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/articles/{slug}/comments",
+    tag = "comments",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    request_body = CommentBody<AddComment>,
+    responses(
+        (status = 201, description = "Created comment", body = CommentBody,
+            headers(("Location" = String, description = "URL of the new comment"))),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn add_comment(
-    state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(slug): Path<String>,
+    mut tx: Tx,
     Json(req): Json<CommentBody<AddComment>>,
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<CommentBody>)> {
+    let comment_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            insert into article_comment(article_id, user_id, body)
+            select article_id, $1, $2
+            from article
+            where slug = $3 and (published or user_id = $1)
+            returning comment_id
+        "#,
+        claims.sub,
+        req.comment.body,
+        slug
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .on_constraint("article_comment_user_id_fkey", |_| Error::Unauthorized)?
+    .ok_or(Error::NotFound)?;
+
+    let comment = sqlx::query_as!(
+        CommentFromQuery,
+        // language=PostgreSQL
+        r#"
+            select
+                comment_id,
+                comment.created_at,
+                comment.updated_at,
+                comment.body,
+                author.username author_username,
+                author.bio author_bio,
+                author.image author_image,
+                false "following_author!"
+            from article_comment comment
+            inner join "user" author using (user_id)
+            where comment_id = $1
+        "#,
+        comment_id
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .into_comment();
+
+    Ok((
+        StatusCode::CREATED,
+        [(
+            LOCATION,
+            format!("/api/articles/{slug}/comments/{}", comment.id),
+        )],
+        Json(CommentBody { comment }),
+    ))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/articles/{slug}/comments/{comment_id}",
+    tag = "comments",
+    security(("token" = [])),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+        ("comment_id" = i64, Path, description = "Comment id")
+    ),
+    request_body = CommentBody<UpdateComment>,
+    responses(
+        (status = 200, description = "Updated comment", body = CommentBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn update_comment(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((slug, comment_id)): Path<(String, i64)>,
+    Json(req): Json<CommentBody<UpdateComment>>,
 ) -> Result<Json<CommentBody>> {
     let comment = sqlx::query_as!(
         CommentFromQuery,
         // language=PostgreSQL
         r#"
-            with inserted_comment as (
-                insert into article_comment(article_id, user_id, body)
-                select article_id, $1, $2
-                from article
-                where slug = $3
+            with updated_comment as (
+                update article_comment
+                set body = $4
+                where
+                    comment_id = $1
+                    and article_id = (select article_id from article where slug = $2)
+                    and user_id = $3
                 returning comment_id, created_at, updated_at, body
             )
             select
@@ -148,21 +328,62 @@ async fn add_comment(
                 author.bio author_bio,
                 author.image author_image,
                 false "following_author!"
-            from inserted_comment comment
-            inner join "user" author on user_id = $1
+            from updated_comment comment
+            inner join "user" author on user_id = $3
         "#,
+        comment_id,
+        slug,
         claims.sub,
-        req.comment.body,
-        slug
+        req.comment.body
     )
     .fetch_optional(&state.db)
-    .await?
-    .ok_or(Error::NotFound)?
-    .into_comment();
+    .await?;
+
+    if let Some(comment) = comment {
+        return Ok(Json(CommentBody {
+            comment: comment.into_comment(),
+        }));
+    }
 
-    Ok(Json(CommentBody { comment }))
+    let existed = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            select exists(
+                select 1 from article_comment
+                inner join article using (article_id)
+                where comment_id = $1 and slug = $2 and (article.published or article.user_id = $3)
+            ) "existed!"
+        "#,
+        comment_id,
+        slug,
+        claims.sub
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    if existed {
+        Err(Error::Forbidden)
+    } else {
+        Err(Error::NotFound)
+    }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{slug}/comments/{comment_id}",
+    tag = "comments",
+    security(("token" = [])),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+        ("comment_id" = i64, Path, description = "Comment id")
+    ),
+    responses(
+        (status = 200, description = "Comment deleted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn delete_comment(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
@@ -183,7 +404,7 @@ async fn delete_comment(
                 exists(
                     select 1 from article_comment
                     inner join article using (article_id)
-                    where comment_id = $1 and slug = $2
+                    where comment_id = $1 and slug = $2 and (article.published or article.user_id = $3)
                 ) "existed!",
                 exists(select 1 from deleted_comment) "deleted!"
         "#,
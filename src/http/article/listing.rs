@@ -1,63 +1,327 @@
-use super::{Article, ArticleFromQuery, Claims, Result};
+use super::{normalize_tags, Article, ArticleSummary, Claims, Error, Result};
+use crate::http::errors::Errors;
+use crate::http::extractor::{Json, Path, Query};
 use crate::http::AppState;
-use axum::extract::{Query, State};
-use axum::{Extension, Json};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
 use futures::TryStreamExt;
-use serde::{Deserialize, Serialize};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
 use time::OffsetDateTime;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Deserialize, Default)]
+/// `cursor` and `offset` can be combined, in which case the offset is applied
+/// to the articles past the cursor. The cursor only applies to the `newest` and
+/// `oldest` sorts, `most_favorited` pages with `offset` alone.
+#[derive(Deserialize, Default, IntoParams)]
 #[serde(default)]
+#[into_params(parameter_in = Query)]
 pub struct ListArticlesQuery {
-    tag: Option<String>,
+    /// Repeat to filter by several tags
+    #[serde(rename = "tag")]
+    #[param(rename = "tag")]
+    tags: Vec<String>,
+    /// Whether articles need `all` of the tags (the default) or `any` of them
+    tag_match: Option<String>,
     author: Option<String>,
     favorited: Option<String>,
-    cursor: Option<OffsetDateTime>,
+    /// Only articles by authors the viewer follows, requires a token
+    following: Option<bool>,
+    /// Leave out the viewer's own articles, requires a token
+    exclude_self: Option<bool>,
+    #[serde(rename = "query")]
+    search: Option<String>,
+    /// One of `newest`, `oldest` or `most_favorited`
+    sort: Option<String>,
+    /// Include each article's `body`, left out by default
+    include_body: Option<bool>,
+    #[param(value_type = Option<String>)]
+    cursor: Option<ArticleCursor>,
+    limit: Option<i64>,
+    offset: Option<i64>,
 }
 
-#[derive(Default, Deserialize)]
+/// Where the previous page ended: the `createdAt` of its last article, optionally followed by a
+/// comma and that article's slug. Without the slug, articles created at the same instant as the
+/// last one are skipped.
+pub struct ArticleCursor {
+    created_at: OffsetDateTime,
+    slug: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for ArticleCursor {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        let (created_at, slug) = match value.rsplit_once(',') {
+            Some((created_at, slug)) => (created_at, Some(slug.to_owned())),
+            None => (value.as_str(), None),
+        };
+        let created_at = OffsetDateTime::deserialize(
+            IntoDeserializer::<D::Error>::into_deserializer(created_at),
+        )?;
+
+        Ok(Self { created_at, slug })
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+enum ArticleSort {
+    #[default]
+    Newest,
+    Oldest,
+    MostFavorited,
+}
+
+impl ArticleSort {
+    fn parse(value: Option<&str>) -> Result<Self> {
+        match value {
+            None => Ok(Self::default()),
+            Some("newest") => Ok(Self::Newest),
+            Some("oldest") => Ok(Self::Oldest),
+            Some("most_favorited") => Ok(Self::MostFavorited),
+            Some(_) => Err(Error::unprocessable_entity([(
+                "sort",
+                "must be one of newest, oldest, most_favorited",
+            )])),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::MostFavorited => "most_favorited",
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+enum TagMatch {
+    #[default]
+    All,
+    Any,
+}
+
+impl TagMatch {
+    fn parse(value: Option<&str>) -> Result<Self> {
+        match value {
+            None => Ok(Self::default()),
+            Some("all") => Ok(Self::All),
+            Some("any") => Ok(Self::Any),
+            Some(_) => Err(Error::unprocessable_entity([(
+                "tag_match",
+                "must be one of all, any",
+            )])),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Any => "any",
+        }
+    }
+}
+
+#[derive(Default, Deserialize, IntoParams)]
 #[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct SearchArticlesQuery {
+    /// Words to look for in the title, description and body
+    q: Option<String>,
+    /// Repeat to require several tags
+    #[serde(rename = "tag")]
+    #[param(rename = "tag")]
+    tags: Vec<String>,
+    author: Option<String>,
+    /// Include each article's `body`, left out by default
+    include_body: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
 pub struct FeedArticlesQuery {
-    cursor: Option<OffsetDateTime>,
+    /// Include each article's `body`, left out by default
+    include_body: Option<bool>,
+    #[param(value_type = Option<String>)]
+    cursor: Option<ArticleCursor>,
+    limit: Option<i64>,
 }
 
-#[derive(Serialize)]
+#[derive(Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct RelatedArticlesQuery {
+    /// Include each article's `body`, left out by default
+    include_body: Option<bool>,
+    limit: Option<i64>,
+}
+
+#[derive(Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct DraftsQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct ProfileArticlesQuery {
+    /// Include each article's `body`, left out by default
+    include_body: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct FavoritesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BatchArticles {
+    slugs: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct MultipleArticlesBody {
+pub struct MultipleArticlesBody<T = Article> {
+    articles: Vec<T>,
+    articles_count: i64,
+}
+
+impl MultipleArticlesBody {
+    /// Drops the bodies unless `include_body` is set.
+    fn into_response(self, include_body: bool) -> Response {
+        if include_body {
+            return Json(self).into_response();
+        }
+
+        Json(MultipleArticlesBody {
+            articles: self
+                .articles
+                .into_iter()
+                .map(ArticleSummary::from)
+                .collect(),
+            articles_count: self.articles_count,
+        })
+        .into_response()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResultsBody<T = Article> {
+    articles: Vec<SearchResult<T>>,
+    articles_count: i64,
+}
+
+/// An article along with how well it matched the search, higher is better.
+#[derive(Serialize, ToSchema)]
+pub struct SearchResult<T = Article> {
+    #[serde(flatten)]
+    article: T,
+    rank: f32,
+}
+
+impl SearchResultsBody {
+    /// Drops the bodies unless `include_body` is set.
+    fn into_response(self, include_body: bool) -> Response {
+        if include_body {
+            return Json(self).into_response();
+        }
+
+        Json(SearchResultsBody {
+            articles: self
+                .articles
+                .into_iter()
+                .map(|result| SearchResult {
+                    article: ArticleSummary::from(result.article),
+                    rank: result.rank,
+                })
+                .collect(),
+            articles_count: self.articles_count,
+        })
+        .into_response()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArticlesByTagBody {
+    tags: Vec<TagArticles>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TagArticles {
+    tag: String,
     articles: Vec<Article>,
-    articles_count: usize,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/articles",
+    tag = "articles",
+    security((), ("token" = [])),
+    params(ListArticlesQuery),
+    responses(
+        (status = 200, description = "Matching articles", body = MultipleArticlesBody<ArticleSummary>),
+        (status = 401, description = "`following` or `exclude_self` without a valid token"),
+        (status = 422, description = "Invalid query", body = Errors),
+    )
+)]
 pub(super) async fn list_articles(
     state: State<AppState>,
     Extension(maybe_claims): Extension<Option<Claims>>,
-    query: Query<ListArticlesQuery>,
-) -> Result<Json<MultipleArticlesBody>> {
-    let articles: Vec<_> = sqlx::query_as!(
-        ArticleFromQuery,
-        // language=PostgreSQL
+    Query(query): Query<ListArticlesQuery>,
+) -> Result<Response> {
+    let sort = ArticleSort::parse(query.sort.as_deref())?;
+    let tag_match = TagMatch::parse(query.tag_match.as_deref())?;
+    let following = query.following.unwrap_or(false);
+    let exclude_self = query.exclude_self.unwrap_or(false);
+    let viewer = maybe_claims.as_ref().map(|claims| claims.sub);
+    if viewer.is_none() && (following || exclude_self) {
+        return Err(Error::Unauthorized);
+    }
+    // Stored tags are normalized, so `Rust` has to be looked up as `rust`.
+    let tags = normalize_tags(query.tags);
+
+    let articles: Vec<_> = select_articles!(
         r#"
-            select
-                slug,
-                title,
-                description,
-                body,
-                tag_list,
-                article.created_at,
-                article.updated_at,
-                exists(select 1 from article_favorite where article_id = article.article_id and user_id = $1) "favorited!",
-                (select count(*) from article_favorite fav where fav.article_id = article.article_id) "favorites_count!",
-                author.username author_username,
-                author.bio author_bio,
-                author.image author_image,
-                exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
-            from article
-            inner join "user" author using (user_id)
-            where (
-                $2::timestamptz is NULL or $2 > article.created_at
+            where article.published
+            and (
+                $2::timestamptz is NULL
+                or (
+                    $9 = 'newest'
+                    and (article.created_at < $2 or (article.created_at = $2 and article.slug < $13))
+                )
+                or (
+                    $9 = 'oldest'
+                    and (article.created_at > $2 or (article.created_at = $2 and article.slug > $13))
+                )
+                or $9 = 'most_favorited'
             )
             and (
-                $3::text is null or tag_list @> array[$3]
+                cardinality($3::text[]) = 0
+                or article.article_id in (
+                    select article_tag.article_id
+                    from article_tag inner join tag using (tag_id)
+                    where name = any($3)
+                    group by article_tag.article_id
+                    having $10 = 'any' or count(*) = cardinality($3)
+                )
             )
             and (
                 $4::text is null or author.username = $4
@@ -68,71 +332,674 @@ pub(super) async fn list_articles(
                     from "user"
                     inner join article_favorite using (user_id)
                     where username = $5
+                    and article_favorite.article_id = article.article_id
                 )
             )
-            order by article.created_at desc
-            limit 20;
+            and (
+                $8::text is null
+                or to_tsvector('english', title || ' ' || description || ' ' || body)
+                    @@ plainto_tsquery('english', $8)
+            )
+            and (
+                not $11 or exists(
+                    select 1 from follow
+                    where followed_user_id = article.user_id and following_user_id = $1
+                )
+            )
+            and (
+                not $12 or article.user_id <> $1
+            )
+            order by
+                case when $9 = 'most_favorited' then favorites.count end desc,
+                case when $9 = 'oldest' then article.created_at end asc,
+                case when $9 = 'oldest' then article.slug end asc,
+                article.created_at desc,
+                article.slug desc
+            limit $6
+            offset $7;
         "#,
-        maybe_claims.as_ref().map(|claims| claims.sub),
-        query.cursor,
-        query.tag,
+        viewer,
+        query.cursor.as_ref().map(|cursor| cursor.created_at),
+        &tags,
         query.author,
-        query.favorited
+        query.favorited,
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0),
+        query.search,
+        sort.as_str(),
+        tag_match.as_str(),
+        following,
+        exclude_self,
+        query
+            .cursor
+            .as_ref()
+            .and_then(|cursor| cursor.slug.as_deref())
     )
     .fetch(&state.db)
-    .map_ok(ArticleFromQuery::into_article)
+    .map_ok(|article| article.into_article(&state.config))
     .try_collect()
     .await?;
 
-    Ok(Json(MultipleArticlesBody {
-        articles_count: articles.len(),
+    let articles_count = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            select count(*) "count!"
+            from article
+            inner join "user" author using (user_id)
+            where article.published
+            and (
+                cardinality($1::text[]) = 0
+                or article.article_id in (
+                    select article_tag.article_id
+                    from article_tag inner join tag using (tag_id)
+                    where name = any($1)
+                    group by article_tag.article_id
+                    having $5 = 'any' or count(*) = cardinality($1)
+                )
+            )
+            and (
+                $2::text is null or author.username = $2
+            )
+            and (
+                $3::text is null or exists(
+                    select 1
+                    from "user"
+                    inner join article_favorite using (user_id)
+                    where username = $3
+                    and article_favorite.article_id = article.article_id
+                )
+            )
+            and (
+                $4::text is null
+                or to_tsvector('english', title || ' ' || description || ' ' || body)
+                    @@ plainto_tsquery('english', $4)
+            )
+            and (
+                not $7 or exists(
+                    select 1 from follow
+                    where followed_user_id = article.user_id and following_user_id = $6
+                )
+            )
+            and (
+                not $8 or article.user_id <> $6
+            )
+        "#,
+        &tags,
+        query.author,
+        query.favorited,
+        query.search,
+        tag_match.as_str(),
+        viewer,
+        following,
+        exclude_self
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let body = MultipleArticlesBody {
         articles,
-    }))
+        articles_count,
+    };
+
+    Ok(body.into_response(query.include_body.unwrap_or(false)))
 }
 
-pub(super) async fn feed_articles(
+/// Ranked by relevance to `q`, matches in the title counting more than in the description, and
+/// those more than in the body. Equally relevant articles come newest first.
+#[utoipa::path(
+    get,
+    path = "/api/articles/search",
+    tag = "articles",
+    security((), ("token" = [])),
+    params(SearchArticlesQuery),
+    responses(
+        (status = 200, description = "Matching articles, most relevant first", body = SearchResultsBody<ArticleSummary>),
+        (status = 422, description = "Missing `q`", body = Errors),
+    )
+)]
+pub(super) async fn search_articles(
     state: State<AppState>,
-    Extension(claims): Extension<Claims>,
-    query: Query<FeedArticlesQuery>,
-) -> Result<Json<MultipleArticlesBody>> {
-    let articles: Vec<_> = sqlx::query_as!(
-        ArticleFromQuery,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Query(query): Query<SearchArticlesQuery>,
+) -> Result<Response> {
+    let q = query
+        .q
+        .filter(|q| !q.trim().is_empty())
+        .ok_or_else(|| Error::unprocessable_entity([("q", "can't be blank")]))?;
+    let tags = normalize_tags(query.tags);
+
+    let ranked = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            select article_id, ts_rank(
+                setweight(to_tsvector('english', title), 'A')
+                    || setweight(to_tsvector('english', description), 'B')
+                    || setweight(to_tsvector('english', body), 'C'),
+                plainto_tsquery('english', $1)
+            ) "rank!"
+            from article
+            inner join "user" author using (user_id)
+            where article.published
+            and to_tsvector('english', title || ' ' || description || ' ' || body)
+                @@ plainto_tsquery('english', $1)
+            and (
+                cardinality($2::text[]) = 0
+                or article.article_id in (
+                    select article_tag.article_id
+                    from article_tag inner join tag using (tag_id)
+                    where name = any($2)
+                    group by article_tag.article_id
+                    having count(*) = cardinality($2)
+                )
+            )
+            and ($3::text is null or author.username = $3)
+            order by "rank!" desc, article.created_at desc, article.slug desc
+            limit $4
+            offset $5
+        "#,
+        q,
+        &tags,
+        query.author,
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0)
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let articles_count = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
-            select
-                slug,
-                title,
-                description,
-                body,
-                tag_list,
-                article.created_at,
-                article.updated_at,
-                exists(select 1 from article_favorite where article_id = article.article_id and user_id = $1) "favorited!",
-                (select count(*) from article_favorite fav where fav.article_id = article.article_id) "favorites_count!",
-                author.username author_username,
-                author.bio author_bio,
-                author.image author_image,
-                true "following_author!"
-            from follow
-            inner join article on followed_user_id = article.user_id
+            select count(*) "count!"
+            from article
             inner join "user" author using (user_id)
-            where (
-                following_user_id = $1
+            where article.published
+            and to_tsvector('english', title || ' ' || description || ' ' || body)
+                @@ plainto_tsquery('english', $1)
+            and (
+                cardinality($2::text[]) = 0
+                or article.article_id in (
+                    select article_tag.article_id
+                    from article_tag inner join tag using (tag_id)
+                    where name = any($2)
+                    group by article_tag.article_id
+                    having count(*) = cardinality($2)
+                )
+            )
+            and ($3::text is null or author.username = $3)
+        "#,
+        q,
+        &tags,
+        query.author
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let article_ids: Vec<_> = ranked.iter().map(|row| row.article_id).collect();
+    let ranks: HashMap<_, _> = ranked
+        .into_iter()
+        .map(|row| (row.article_id, row.rank))
+        .collect();
+
+    let articles: Vec<_> = select_articles!(
+        r#"
+            where article.article_id = any($2)
+            order by array_position($2, article.article_id)
+        "#,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        &article_ids
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let body = SearchResultsBody {
+        articles: articles
+            .into_iter()
+            .map(|article| SearchResult {
+                rank: ranks[&article.id],
+                article,
+            })
+            .collect(),
+        articles_count,
+    };
+
+    Ok(body.into_response(query.include_body.unwrap_or(false)))
+}
+
+/// Published articles by followed authors, newest first. `articlesCount` counts all of them, not
+/// just those after `cursor`.
+#[utoipa::path(
+    get,
+    path = "/api/articles/feed",
+    tag = "articles",
+    security(("token" = [])),
+    params(FeedArticlesQuery),
+    responses(
+        (status = 200, description = "Articles by followed authors", body = MultipleArticlesBody<ArticleSummary>),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+pub(super) async fn feed_articles(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<FeedArticlesQuery>,
+) -> Result<Response> {
+    let articles: Vec<_> = select_articles!(
+        r#"
+            where article.published
+            and exists(
+                select 1 from follow
+                where followed_user_id = article.user_id and following_user_id = $1
             ) and (
-                $2::timestamptz is NULL or $2 > article.created_at
+                $2::timestamptz is NULL
+                or article.created_at < $2
+                or (article.created_at = $2 and article.slug < $3)
             )
-            limit 20
+            order by article.created_at desc, article.slug desc
+            limit $4
         "#,
         claims.sub,
-        query.cursor
+        query.cursor.as_ref().map(|cursor| cursor.created_at),
+        query
+            .cursor
+            .as_ref()
+            .and_then(|cursor| cursor.slug.as_deref()),
+        state.config.page_size(query.limit)
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let articles_count = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            select count(*) "count!"
+            from article
+            where published
+            and exists(
+                select 1 from follow
+                where followed_user_id = article.user_id and following_user_id = $1
+            )
+        "#,
+        claims.sub
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let body = MultipleArticlesBody {
+        articles,
+        articles_count,
+    };
+
+    Ok(body.into_response(query.include_body.unwrap_or(false)))
+}
+
+/// Other articles sharing tags with this one, those sharing the most first. Articles without a
+/// tag in common are left out, so an untagged article has no related ones.
+#[utoipa::path(
+    get,
+    path = "/api/articles/{slug}/related",
+    tag = "articles",
+    security((), ("token" = [])),
+    params(("slug" = String, Path, description = "Article slug"), RelatedArticlesQuery),
+    responses(
+        (status = 200, description = "Related articles", body = MultipleArticlesBody<ArticleSummary>),
+        (status = 404, description = "Not found"),
+    )
+)]
+pub(super) async fn related_articles(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Path(slug): Path<String>,
+    Query(query): Query<RelatedArticlesQuery>,
+) -> Result<Response> {
+    let viewer = maybe_claims.as_ref().map(|claims| claims.sub);
+
+    let article_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select article_id from article where slug = $1 and (published or user_id = $2)"#,
+        slug,
+        viewer
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let articles: Vec<_> = select_articles!(
+        r#"
+            inner join lateral (
+                select count(*) "count"
+                from article_tag theirs
+                inner join article_tag mine using (tag_id)
+                where theirs.article_id = article.article_id and mine.article_id = $2
+            ) shared_tags on true
+            where article.published
+            and article.article_id <> $2
+            and shared_tags.count > 0
+            order by shared_tags.count desc, article.created_at desc, article.slug desc
+            limit $3
+        "#,
+        viewer,
+        article_id,
+        state.config.page_size(query.limit)
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let body = MultipleArticlesBody {
+        articles_count: articles.len() as i64,
+        articles,
+    };
+
+    Ok(body.into_response(query.include_body.unwrap_or(false)))
+}
+
+/// The viewer's unpublished articles, most recently edited first.
+#[utoipa::path(
+    get,
+    path = "/api/articles/drafts",
+    tag = "articles",
+    security(("token" = [])),
+    params(DraftsQuery),
+    responses(
+        (status = 200, description = "The viewer's drafts", body = MultipleArticlesBody),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+pub(super) async fn draft_articles(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<DraftsQuery>,
+) -> Result<Json<MultipleArticlesBody>> {
+    let articles: Vec<_> = select_articles!(
+        r#"
+            where article.user_id = $1 and not article.published
+            order by article.updated_at desc, article.slug
+            limit $2
+            offset $3
+        "#,
+        claims.sub,
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0)
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let articles_count = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select count(*) "count!" from article where user_id = $1 and not published"#,
+        claims.sub
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(MultipleArticlesBody {
+        articles,
+        articles_count,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/articles/by-tag",
+    tag = "articles",
+    security((), ("token" = [])),
+    responses((status = 200, description = "Latest articles for the most used tags", body = ArticlesByTagBody))
+)]
+pub(super) async fn articles_by_tag(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+) -> Result<Json<ArticlesByTagBody>> {
+    // Every group in one go: the latest articles of each top tag, as (tag, article) pairs in
+    // the order of the response.
+    let entries = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            with top_tag as (
+                select tag_id, name, row_number() over (order by count(*) desc, name) "rank"
+                from tag
+                inner join article_tag using (tag_id)
+                inner join article using (article_id)
+                where article.published
+                group by tag_id, name
+                order by count(*) desc, name
+                limit $1
+            )
+            select name "tag!", article_id "article_id!"
+            from (
+                select
+                    top_tag.name,
+                    top_tag.rank,
+                    article.article_id,
+                    row_number() over (
+                        partition by top_tag.tag_id
+                        order by article.created_at desc, article.slug desc
+                    ) "position"
+                from top_tag
+                inner join article_tag using (tag_id)
+                inner join article using (article_id)
+                where article.published
+            ) tagged
+            where position <= $2
+            order by rank, position
+        "#,
+        state.config.articles_by_tag_tag_count,
+        state.config.articles_by_tag_article_count
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let article_ids: Vec<_> = entries.iter().map(|entry| entry.article_id).collect();
+
+    // One row per entry, duplicates included, so that the rows line up with `entries`.
+    let articles: Vec<_> = select_articles!(
+        r#"
+            inner join unnest($2::uuid[]) with ordinality entry(article_id, position)
+                on entry.article_id = article.article_id
+            order by entry.position
+        "#,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        &article_ids
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let mut groups: Vec<TagArticles> = Vec::new();
+    let mut articles = articles.into_iter().peekable();
+
+    for entry in entries {
+        // Skips entries whose article was deleted in between.
+        let Some(article) = articles.next_if(|article| article.id == entry.article_id) else {
+            continue;
+        };
+
+        match groups.last_mut() {
+            Some(group) if group.tag == entry.tag => group.articles.push(article),
+            _ => groups.push(TagArticles {
+                tag: entry.tag,
+                articles: vec![article],
+            }),
+        }
+    }
+
+    Ok(Json(ArticlesByTagBody { tags: groups }))
+}
+
+/// Same as `GET /api/articles?author=`, except that an unknown author is a 404 rather than an
+/// empty list.
+#[utoipa::path(
+    get,
+    path = "/api/profiles/{username}/articles",
+    tag = "profiles",
+    security((), ("token" = [])),
+    params(("username" = String, Path, description = "Profile username"), ProfileArticlesQuery),
+    responses(
+        (status = 200, description = "The user's articles, newest first", body = MultipleArticlesBody<ArticleSummary>),
+        (status = 404, description = "Not found"),
+    )
+)]
+pub(super) async fn profile_articles(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Path(username): Path<String>,
+    Query(query): Query<ProfileArticlesQuery>,
+) -> Result<Response> {
+    let user_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select user_id from "user" where username = $1"#,
+        username
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let articles: Vec<_> = select_articles!(
+        r#"
+            where article.user_id = $2 and article.published
+            order by article.created_at desc, article.slug desc
+            limit $3
+            offset $4
+        "#,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        user_id,
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0)
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let articles_count = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select count(*) "count!" from article where user_id = $1 and published"#,
+        user_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    let body = MultipleArticlesBody {
+        articles,
+        articles_count,
+    };
+
+    Ok(body.into_response(query.include_body.unwrap_or(false)))
+}
+
+/// Most recently favorited first.
+#[utoipa::path(
+    get,
+    path = "/api/profiles/{username}/favorites",
+    tag = "profiles",
+    security((), ("token" = [])),
+    params(("username" = String, Path, description = "Profile username"), FavoritesQuery),
+    responses(
+        (status = 200, description = "Articles favorited by the user", body = MultipleArticlesBody),
+        (status = 404, description = "Not found"),
+    )
+)]
+pub(super) async fn profile_favorites(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Path(username): Path<String>,
+    Query(query): Query<FavoritesQuery>,
+) -> Result<Json<MultipleArticlesBody>> {
+    let user_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select user_id from "user" where username = $1"#,
+        username
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let articles: Vec<_> = select_articles!(
+        r#"
+            inner join article_favorite profile_favorite
+                on profile_favorite.article_id = article.article_id
+                and profile_favorite.user_id = $2
+            where article.published
+            order by profile_favorite.created_at desc, article.article_id
+            limit $3
+            offset $4
+        "#,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        user_id,
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0)
+    )
+    .fetch(&state.db)
+    .map_ok(|article| article.into_article(&state.config))
+    .try_collect()
+    .await?;
+
+    let articles_count = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            select count(*) "count!"
+            from article_favorite
+            inner join article using (article_id)
+            where article_favorite.user_id = $1 and article.published
+        "#,
+        user_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+
+    Ok(Json(MultipleArticlesBody {
+        articles,
+        articles_count,
+    }))
+}
+
+/// Unknown slugs are skipped, the rest come back in the requested order.
+#[utoipa::path(
+    post,
+    path = "/api/articles/batch",
+    tag = "articles",
+    security((), ("token" = [])),
+    request_body = BatchArticles,
+    responses(
+        (status = 200, description = "Articles found among the slugs", body = MultipleArticlesBody),
+        (status = 422, description = "Too many slugs", body = Errors),
+    )
+)]
+pub(super) async fn batch_articles(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Json(req): Json<BatchArticles>,
+) -> Result<Json<MultipleArticlesBody>> {
+    let max_slugs = state.config.max_page_size;
+    if req.slugs.len() as i64 > max_slugs {
+        return Err(Error::unprocessable_entity([(
+            "slugs",
+            format!("is too long (maximum is {max_slugs} slugs)"),
+        )]));
+    }
+
+    let articles: Vec<_> = select_articles!(
+        r#"
+            where slug = any($2) and (article.published or article.user_id = $1)
+            order by array_position($2, slug)
+        "#,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        &req.slugs
     )
     .fetch(&state.db)
-    .map_ok(ArticleFromQuery::into_article)
+    .map_ok(|article| article.into_article(&state.config))
     .try_collect()
     .await?;
 
     Ok(Json(MultipleArticlesBody {
-        articles_count: articles.len(),
+        articles_count: articles.len() as i64,
         articles,
     }))
 }
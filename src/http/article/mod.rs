@@ -1,25 +1,88 @@
+/// Selects [`ArticleFromQuery`] rows as seen by the viewer bound to `$1` (nullable).
+///
+/// Callers append the rest of the query (`where`, `order by`, ...) as a string literal and bind
+/// their own parameters from `$2` on; `query_as!` still checks the combined statement.
+macro_rules! select_articles {
+    ($rest:literal $(, $args:expr)* $(,)?) => {
+        sqlx::query_as!(
+            $crate::http::article::ArticleFromQuery,
+            // language=PostgreSQL
+            r#"
+                select
+                    article.article_id id,
+                    slug,
+                    title,
+                    description,
+                    body,
+                    array(
+                        select name from article_tag inner join tag using (tag_id)
+                        where article_tag.article_id = article.article_id
+                        order by name
+                    ) "tag_list!",
+                    article.published,
+                    article.created_at,
+                    article.updated_at,
+                    favorites.favorited "favorited!",
+                    favorites.count "favorites_count!",
+                    (select count(*) from article_comment comment where comment.article_id = article.article_id) "comments_count!",
+                    article.view_count,
+                    author.username author_username,
+                    author.bio author_bio,
+                    author.image author_image,
+                    exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
+                from article
+                inner join "user" author using (user_id)
+                left join lateral (
+                    select count(*) "count", coalesce(bool_or(fav.user_id = $1), false) "favorited"
+                    from article_favorite fav
+                    where fav.article_id = article.article_id
+                ) favorites on true
+            "# + $rest
+            $(, $args)*
+        )
+    };
+}
+
 mod comments;
 mod listing;
 
+pub(super) use comments::CommentsApi;
+
+use super::etag::{self, ETag};
 use super::profiles::Profile;
-use super::{auth, AppState, Error, Result};
+use super::transaction::{self, Tx};
+use super::{auth, idempotency, AppState, Error, Result};
 use crate::config::Config;
 use crate::http::auth::Claims;
-use crate::http::errors::ResultExt;
-use axum::extract::{Path, State};
+use crate::http::errors::{Errors, ResultExt};
+use crate::http::extractor::{Json, Path, Query};
+use axum::extract::State;
+use axum::http::header::LOCATION;
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::Response;
 use axum::routing::{get, post, put};
-use axum::{middleware, Extension, Json, Router};
+use axum::{middleware, Extension, Router};
 use serde::{Deserialize, Serialize};
-use sqlx::{Executor, Postgres};
-use std::sync::Arc;
+use sqlx::{Executor, PgConnection, Postgres};
+use std::borrow::Cow;
 use time::OffsetDateTime;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use uuid::Uuid;
 
-pub fn router(state: Arc<Config>) -> Router<AppState> {
+pub fn router(state: AppState) -> Router<AppState> {
     Router::new()
         .route(
             "/api/articles",
             post(create_article)
+                .route_layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    idempotency::idempotency,
+                ))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/articles/validate",
+            post(validate_article)
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
         )
         .route(
@@ -29,11 +92,37 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
                 auth::maybe_auth,
             )),
         )
+        .route(
+            "/api/articles/search",
+            get(listing::search_articles).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
         .route(
             "/api/articles/feed",
             get(listing::feed_articles)
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
         )
+        .route(
+            "/api/articles/drafts",
+            get(listing::draft_articles)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/articles/batch",
+            post(listing::batch_articles).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
+        .route(
+            "/api/articles/by-tag",
+            get(listing::articles_by_tag).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
         .route(
             "/api/articles/{slug}",
             get(get_article).route_layer(middleware::from_fn_with_state(
@@ -44,219 +133,751 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
         .route(
             "/api/articles/{slug}",
             put(update_article)
+                .patch(patch_article)
                 .delete(delete_article)
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
         )
+        .route(
+            "/api/articles/{slug}/tags",
+            put(replace_article_tags)
+                .route_layer(middleware::from_fn(transaction::transaction))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/articles/{slug}/related",
+            get(listing::related_articles).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
         .route(
             "/api/articles/{slug}/favorite",
             post(favorite_article)
                 .delete(unfavorite_article)
+                .route_layer(middleware::from_fn(transaction::transaction))
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/articles/id/{article_id}/favorite",
+            post(favorite_article_by_id)
+                .delete(unfavorite_article_by_id)
+                .route_layer(middleware::from_fn(transaction::transaction))
                 .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
         )
+        .route(
+            "/api/profiles/{username}/articles",
+            get(listing::profile_articles).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
+        .route(
+            "/api/profiles/{username}/favorites",
+            get(listing::profile_favorites).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
         .route("/api/tags", get(get_tags))
+        .route("/api/tags/counts", get(get_tag_counts))
+        .route("/api/tags/trending", get(get_trending_tags))
+        .route("/api/tags/{tag}/authors", get(get_tag_authors))
         .merge(comments::router(state))
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(OpenApi)]
+#[openapi(paths(
+    create_article,
+    validate_article,
+    listing::list_articles,
+    listing::search_articles,
+    listing::feed_articles,
+    listing::draft_articles,
+    listing::related_articles,
+    listing::articles_by_tag,
+    listing::batch_articles,
+    listing::profile_articles,
+    listing::profile_favorites,
+    get_article,
+    update_article,
+    patch_article,
+    replace_article_tags,
+    delete_article,
+    favorite_article,
+    unfavorite_article,
+    favorite_article_by_id,
+    unfavorite_article_by_id,
+    get_tags,
+    get_tag_counts,
+    get_trending_tags,
+    get_tag_authors
+))]
+pub(super) struct ArticlesApi;
+
+const DEFAULT_TRENDING_DAYS: i32 = 7;
+
+const MAX_TRENDING_DAYS: i32 = 365;
+
+const DEFAULT_TRENDING_LIMIT: i64 = 10;
+
+const MAX_TRENDING_LIMIT: i64 = 100;
+
+#[derive(Serialize, Deserialize, ToSchema)]
 struct ArticleBody<T = Article> {
     article: T,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReplaceTags {
+    tag_list: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ReplacedTagsBody {
+    /// The article's tags after the update, normalized
+    tag_list: Vec<String>,
+    /// Inputs that were left out, in the order they were sent
+    rejected: Vec<RejectedTag>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct RejectedTag {
+    tag: String,
+    reason: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ValidationBody {
+    valid: bool,
+}
+
+#[derive(Serialize, ToSchema)]
 struct TagsBody {
     tags: Vec<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, ToSchema)]
+struct TagCountsBody {
+    tags: Vec<TagCount>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TagCount {
+    tag: String,
+    count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TagAuthorsBody {
+    authors: Vec<TagAuthor>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct TagAuthor {
+    username: String,
+    /// Published articles by this author with the tag
+    count: i64,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+struct GetArticleQuery {
+    /// `html` to also get the body rendered from markdown as `bodyHtml`
+    render: Option<String>,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+struct TrendingTagsQuery {
+    /// Only count articles created in this many most recent days, 7 by default
+    days: Option<i32>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct CreateArticle {
     title: String,
     description: String,
     body: String,
     tag_list: Vec<String>,
+    /// `false` saves a draft that only its author can see, `true` by default
+    published: Option<bool>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 struct UpdateArticle {
     title: Option<String>,
     description: Option<String>,
     body: Option<String>,
+    tag_list: Option<Vec<String>>,
+    published: Option<bool>,
+    /// Whether a new title also gets a new slug. Defaults to `REGENERATE_SLUG_ON_TITLE_CHANGE`,
+    /// which is off so that links to the article keep working.
+    regenerate_slug: Option<bool>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 struct Article {
+    id: Uuid,
     slug: String,
     title: String,
     description: String,
     body: String,
     tag_list: Vec<String>,
+    published: bool,
     created_at: OffsetDateTime,
     updated_at: OffsetDateTime,
     favorited: bool,
     favorites_count: i64,
+    comments_count: i64,
+    view_count: i64,
+    reading_time_minutes: i64,
+    /// `body` rendered to sanitized HTML, only sent when asked for with `render=html`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_html: Option<String>,
     author: Profile,
 }
 
+/// [`Article`] without its body, for listings that only show a preview.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ArticleSummary {
+    id: Uuid,
+    slug: String,
+    title: String,
+    description: String,
+    tag_list: Vec<String>,
+    published: bool,
+    created_at: OffsetDateTime,
+    updated_at: OffsetDateTime,
+    favorited: bool,
+    favorites_count: i64,
+    comments_count: i64,
+    view_count: i64,
+    reading_time_minutes: i64,
+    author: Profile,
+}
+
+impl From<Article> for ArticleSummary {
+    fn from(article: Article) -> Self {
+        Self {
+            id: article.id,
+            slug: article.slug,
+            title: article.title,
+            description: article.description,
+            tag_list: article.tag_list,
+            published: article.published,
+            created_at: article.created_at,
+            updated_at: article.updated_at,
+            favorited: article.favorited,
+            favorites_count: article.favorites_count,
+            comments_count: article.comments_count,
+            view_count: article.view_count,
+            reading_time_minutes: article.reading_time_minutes,
+            author: article.author,
+        }
+    }
+}
+
 struct ArticleFromQuery {
+    id: Uuid,
     slug: String,
     title: String,
     description: String,
     body: String,
     tag_list: Vec<String>,
+    published: bool,
     created_at: OffsetDateTime,
     updated_at: OffsetDateTime,
     favorited: bool,
     favorites_count: i64,
+    comments_count: i64,
+    view_count: i64,
     author_username: String,
     author_bio: String,
     author_image: Option<String>,
     following_author: bool,
 }
 
+/// Whole minutes at `words_per_minute`, rounded down but never below one.
+fn reading_time_minutes(body: &str, words_per_minute: u32) -> i64 {
+    let words = body.split_whitespace().count() as i64;
+
+    (words / i64::from(words_per_minute)).max(1)
+}
+
 impl ArticleFromQuery {
-    fn into_article(self) -> Article {
+    fn into_article(self, config: &Config) -> Article {
+        let reading_time_minutes =
+            reading_time_minutes(&self.body, config.reading_words_per_minute);
+
         Article {
+            id: self.id,
             slug: self.slug,
             title: self.title,
             description: self.description,
             body: self.body,
             tag_list: self.tag_list,
+            published: self.published,
             created_at: self.created_at,
             updated_at: self.updated_at,
             favorited: self.favorited,
             favorites_count: self.favorites_count,
+            comments_count: self.comments_count,
+            view_count: self.view_count,
+            reading_time_minutes,
+            body_html: None,
             author: Profile {
                 username: self.author_username,
                 bio: self.author_bio,
                 image: self.author_image,
                 following: self.following_author,
+                followers_count: None,
+                following_count: None,
+                created_at: None,
             },
         }
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/articles",
+    tag = "articles",
+    security(("token" = [])),
+    request_body = ArticleBody<CreateArticle>,
+    responses(
+        (status = 201, description = "Created article", body = ArticleBody,
+            headers(("Location" = String, description = "URL of the new article"))),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 422, description = "Validation failed", body = Errors),
+        (status = 429, description = "Article creation cooldown has not elapsed"),
+    )
+)]
 async fn create_article(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Json(mut req): Json<ArticleBody<CreateArticle>>,
-) -> Result<Json<ArticleBody>> {
-    let slug = slugify(&req.article.title);
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<ArticleBody>)> {
+    if let Some(cooldown) = state.config.article_creation_cooldown_seconds {
+        let is_admin = sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"select is_admin from "user" where user_id = $1"#,
+            claims.sub
+        )
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or(Error::Unauthorized)?;
 
-    req.article.tag_list.sort();
+        // Admins bypass the cooldown.
+        if !is_admin {
+            check_creation_cooldown(&state.db, claims.sub, cooldown).await?;
+        }
+    }
+
+    validate_new_article(&req.article, &state.config)?;
+
+    req.article.tag_list = normalize_tags(req.article.tag_list);
+
+    let mut tx = state.db.begin().await?;
+
+    for slug in slug_candidates(&req.article.title) {
+        let article_id = sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"
+                insert into article (user_id, slug, title, description, body, published)
+                values ($1, $2, $3, $4, $5, $6)
+                on conflict (slug) do nothing
+                returning article_id
+            "#,
+            claims.sub,
+            slug,
+            req.article.title,
+            req.article.description,
+            req.article.body,
+            req.article.published.unwrap_or(true)
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .on_constraint("article_user_id_fkey", |_| Error::Unauthorized)?;
+
+        if let Some(article_id) = article_id {
+            set_article_tags(&mut *tx, article_id, &req.article.tag_list).await?;
+            let article = article_by_id(&mut *tx, &state.config, claims.sub, article_id).await?;
+
+            tx.commit().await?;
+
+            return Ok((
+                StatusCode::CREATED,
+                [(LOCATION, format!("/api/articles/{}", article.slug))],
+                Json(ArticleBody { article }),
+            ));
+        }
+    }
+
+    Err(Error::unprocessable_entity([(
+        "slug",
+        "could not generate a unique article slug",
+    )]))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/articles/validate",
+    tag = "articles",
+    security(("token" = [])),
+    request_body = ArticleBody<CreateArticle>,
+    responses(
+        (status = 200, description = "Article would be accepted", body = ValidationBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 422, description = "Validation failed", body = Errors),
+    )
+)]
+async fn validate_article(
+    state: State<AppState>,
+    Json(req): Json<ArticleBody<CreateArticle>>,
+) -> Result<Json<ValidationBody>> {
+    validate_new_article(&req.article, &state.config)?;
+
+    Ok(Json(ValidationBody { valid: true }))
+}
+
+fn validate_new_article(article: &CreateArticle, config: &Config) -> Result<()> {
+    let mut errors = Vec::new();
+
+    if article.title.trim().is_empty() {
+        errors.push(("title", "can't be blank".to_string()));
+    }
+    if article.description.trim().is_empty() {
+        errors.push(("description", "can't be blank".to_string()));
+    }
+    if article.body.trim().is_empty() {
+        errors.push(("body", "can't be blank".to_string()));
+    }
+    errors.extend(body_error(&article.body, config));
+    errors.extend(tag_errors(&article.tag_list, config));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::unprocessable_entity(errors))
+    }
+}
+
+fn validate_article_update(article: &UpdateArticle, config: &Config) -> Result<()> {
+    let fields = [
+        ("title", &article.title),
+        ("description", &article.description),
+        ("body", &article.body),
+    ];
+    let mut errors: Vec<_> = fields
+        .into_iter()
+        .filter(|(_, value)| value.as_deref().is_some_and(|v| v.trim().is_empty()))
+        .map(|(field, _)| (field, "can't be blank".to_string()))
+        .collect();
+    if let Some(body) = &article.body {
+        errors.extend(body_error(body, config));
+    }
+    if let Some(tags) = &article.tag_list {
+        errors.extend(tag_errors(tags, config));
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::unprocessable_entity(errors))
+    }
+}
+
+/// Counted in characters rather than bytes, so non-ASCII text is not penalized.
+fn body_error(body: &str, config: &Config) -> Option<(&'static str, String)> {
+    (body.chars().count() > config.max_article_body_length).then(|| {
+        (
+            "body",
+            format!(
+                "is too long (maximum is {} characters)",
+                config.max_article_body_length
+            ),
+        )
+    })
+}
+
+/// Limits apply to the tags as they will be stored, after [`normalize_tags`].
+fn tag_errors(tags: &[String], config: &Config) -> Vec<(&'static str, String)> {
+    let mut errors = Vec::new();
+
+    if tags.iter().any(|tag| tag.trim().is_empty()) {
+        errors.push(("tagList", "can't contain blank tags".to_string()));
+    }
 
-    let article = sqlx::query_as!(
-        ArticleFromQuery,
+    let tags = normalize_tags(tags.to_vec());
+    if tags.len() > config.max_tags_per_article {
+        errors.push((
+            "tagList",
+            format!(
+                "is too long (maximum is {} tags)",
+                config.max_tags_per_article
+            ),
+        ));
+    }
+    if tags
+        .iter()
+        .any(|tag| tag.chars().count() > config.max_tag_length)
+    {
+        errors.push((
+            "tagList",
+            format!(
+                "can't contain tags longer than {} characters",
+                config.max_tag_length
+            ),
+        ));
+    }
+
+    errors
+}
+
+/// Lowercases tags and collapses runs of whitespace to a single space, so that
+/// `"Rust"`, `" rust "` and `"rust"` are stored as one tag. The result is sorted and deduplicated.
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<_> = tags.iter().map(|tag| normalize_tag(tag)).collect();
+
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+async fn check_creation_cooldown(
+    e: impl Executor<'_, Database = Postgres>,
+    user_id: Uuid,
+    cooldown_seconds: u64,
+) -> Result<()> {
+    let retry_after = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
-            with inserted_article as (
-                insert into article (user_id, slug, title, description, body, tag_list)
-                values ($1, $2, $3, $4, $5, $6)
-                returning
-                    slug,
-                    title,
-                    description,
-                    body,
-                    tag_list,
-                    created_at,
-                    updated_at
-            )
-            select
-                inserted_article.*,
-                false "favorited!",
-                0::int8 "favorites_count!",
-                username author_username,
-                bio author_bio,
-                image author_image,
-                false "following_author!"
-            from inserted_article
-            inner join "user" on user_id = $1
+            select ceil(extract(epoch from max(created_at) + make_interval(secs => $2) - now()))::int8
+            from article
+            where user_id = $1
         "#,
-        claims.sub,
-        slug,
-        req.article.title,
-        req.article.description,
-        req.article.body,
-        &req.article.tag_list[..]
+        user_id,
+        cooldown_seconds as f64
     )
-    .fetch_one(&state.db)
-    .await
-    .on_constraint("article_slug_key", |_| {
-        Error::unprocessable_entity([("slug", format!("duplicate article slug: {}", slug))])
-    })?;
+    .fetch_one(e)
+    .await?;
 
-    Ok(Json(ArticleBody {
-        article: article.into_article(),
-    }))
+    match retry_after {
+        Some(retry_after) if retry_after > 0 => Err(Error::TooManyRequests {
+            retry_after: retry_after as u64,
+        }),
+        _ => Ok(()),
+    }
 }
 
+/// Replaces the whole article, so `title`, `description`, `body`, `tagList` and `published` must all
+/// be present. Unlike the RealWorld spec, where `PUT` is a partial update; that is
+/// [`patch_article`] here.
+#[utoipa::path(
+    put,
+    path = "/api/articles/{slug}",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    request_body = ArticleBody<UpdateArticle>,
+    responses(
+        (status = 200, description = "Updated article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+        (status = 422, description = "Validation failed", body = Errors),
+    )
+)]
 async fn update_article(
+    state: State<AppState>,
+    claims: Extension<Claims>,
+    slug: Path<String>,
+    req: Json<ArticleBody<UpdateArticle>>,
+) -> Result<Json<ArticleBody>> {
+    let article = &req.0.article;
+    let missing: Vec<_> = [
+        ("title", article.title.is_none()),
+        ("description", article.description.is_none()),
+        ("body", article.body.is_none()),
+        ("tagList", article.tag_list.is_none()),
+        ("published", article.published.is_none()),
+    ]
+    .into_iter()
+    .filter(|(_, missing)| *missing)
+    .map(|(field, _)| (field, "can't be blank"))
+    .collect();
+    if !missing.is_empty() {
+        return Err(Error::unprocessable_entity(missing));
+    }
+
+    patch_article(state, claims, slug, req).await
+}
+
+/// Changes only the fields present in the body.
+#[utoipa::path(
+    patch,
+    path = "/api/articles/{slug}",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    request_body = ArticleBody<UpdateArticle>,
+    responses(
+        (status = 200, description = "Updated article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+        (status = 422, description = "Validation failed", body = Errors),
+    )
+)]
+async fn patch_article(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(slug): Path<String>,
     Json(req): Json<ArticleBody<UpdateArticle>>,
 ) -> Result<Json<ArticleBody>> {
-    let new_slug = req.article.title.as_deref().map(slugify);
+    validate_article_update(&req.article, &state.config)?;
+
+    let regenerate_slug = req
+        .article
+        .regenerate_slug
+        .unwrap_or(state.config.regenerate_slug_on_title_change);
+    let tag_list = req.article.tag_list.map(normalize_tags);
+
+    let mut tx = state.db.begin().await?;
 
-    let article = sqlx::query_as!(
-        ArticleFromQuery,
+    let article_id = owned_article_id(&mut *tx, &slug, claims.sub).await?;
+
+    sqlx::query!(
         // language=PostgreSQL
         r#"
-            with permission_check as (
-                select article_id from article
-                where slug = $1 and user_id = $2
-            ),
-            updated_article as (
-                update article
-                    set
-                        slug = coalesce($3, slug),
-                        title = coalesce($4, title),
-                        description = coalesce($5, description),
-                        body = coalesce($6, body)
-                    where slug = $1 and exists(select 1 from permission_check)
-                    returning
-                        slug,
-                        title,
-                        description,
-                        body,
-                        tag_list,
-                        article.created_at,
-                        article.updated_at
-            )
-            select
-                updated_article.*,
-                exists(select 1 from article_favorite where user_id = $2) "favorited!",
-                (select count(*) from article_favorite fav where fav.article_id = (select article_id from permission_check)) "favorites_count!",
-                author.username "author_username",
-                author.bio "author_bio",
-                author.image "author_image",
-                false "following_author!"
-            from updated_article
-                     inner join "user" author on author.user_id = $2
+            update article
+            set
+                title = coalesce($2, title),
+                description = coalesce($3, description),
+                body = coalesce($4, body),
+                published = coalesce($5, published)
+            where article_id = $1
         "#,
-        slug,
-        claims.sub,
-        new_slug,
+        article_id,
         req.article.title,
         req.article.description,
-        req.article.body
+        req.article.body,
+        req.article.published
     )
-    .fetch_one(&state.db)
-    .await
-    .on_constraint("article_slug_key", |_| {
-        Error::unprocessable_entity([
-            ("slug", format!("duplicate article slug: {}", new_slug.unwrap()))
-        ])
-    })
-    .map_err(|e| match e {
-        Error::UnprocessableEntity{ .. } => e,
-        _ => Error::Forbidden
-    })?
-    .into_article();
+    .execute(&mut *tx)
+    .await?;
+
+    if let Some(title) = req.article.title.as_deref().filter(|_| regenerate_slug) {
+        regenerate_article_slug(&mut tx, article_id, title).await?;
+    }
+
+    if let Some(tag_list) = &tag_list {
+        set_article_tags(&mut *tx, article_id, tag_list).await?;
+    }
+
+    let article = article_by_id(&mut *tx, &state.config, claims.sub, article_id).await?;
+
+    tx.commit().await?;
 
     Ok(Json(ArticleBody { article }))
 }
 
+/// Replaces the article's tags with the valid ones among `tagList`.
+///
+/// Unlike the tag list of a create or update, which is rejected as a whole, invalid tags are
+/// skipped and reported under `rejected` while the rest are applied. Tags are taken in the order
+/// sent, so once an article has the maximum number of tags, later ones are the ones rejected.
+#[utoipa::path(
+    put,
+    path = "/api/articles/{slug}/tags",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    request_body = ReplaceTags,
+    responses(
+        (status = 200, description = "Tags applied, with any rejected inputs", body = ReplacedTagsBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn replace_article_tags(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(slug): Path<String>,
+    mut tx: Tx,
+    Json(req): Json<ReplaceTags>,
+) -> Result<Json<ReplacedTagsBody>> {
+    let config = &state.config;
+    let mut tags = Vec::new();
+    let mut rejected = Vec::new();
+
+    for input in req.tag_list {
+        let tag = normalize_tag(&input);
+        let reason = if tag.is_empty() {
+            Some("can't be blank".to_string())
+        } else if tag.chars().count() > config.max_tag_length {
+            Some(format!(
+                "is too long (maximum is {} characters)",
+                config.max_tag_length
+            ))
+        } else if tags.contains(&tag) {
+            continue;
+        } else if tags.len() >= config.max_tags_per_article {
+            Some(format!(
+                "is over the limit of {} tags",
+                config.max_tags_per_article
+            ))
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => rejected.push(RejectedTag { tag: input, reason }),
+            None => tags.push(tag),
+        }
+    }
+
+    tags.sort();
+
+    let article_id = owned_article_id(&mut *tx, &slug, claims.sub).await?;
+    set_article_tags(&mut *tx, article_id, &tags).await?;
+
+    Ok(Json(ReplacedTagsBody {
+        tag_list: tags,
+        rejected,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{slug}",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    responses(
+        (status = 200, description = "Article deleted"),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Not the author"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn delete_article(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
@@ -289,54 +910,163 @@ async fn delete_article(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/articles/{slug}",
+    tag = "articles",
+    security((), ("token" = [])),
+    params(
+        ("slug" = String, Path, description = "Article slug"),
+        GetArticleQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previously fetched copy")
+    ),
+    responses(
+        (status = 200, description = "Article", body = ArticleBody,
+            headers(("ETag" = String))),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 404, description = "Not found"),
+        (status = 422, description = "Unknown `render`", body = Errors),
+    )
+)]
 async fn get_article(
     state: State<AppState>,
     Extension(maybe_claims): Extension<Option<Claims>>,
     Path(slug): Path<String>,
-) -> Result<Json<ArticleBody>> {
-    let article = sqlx::query_as!(
-        ArticleFromQuery,
+    Query(query): Query<GetArticleQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let render_html = match query.render.as_deref() {
+        None => false,
+        Some("html") => true,
+        Some(_) => {
+            return Err(Error::unprocessable_entity([("render", "must be html")]));
+        }
+    };
+    let viewer = maybe_claims.as_ref().map(|claims| claims.sub);
+
+    let article_id = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
-            select
-                slug,
-                title,
-                description,
-                body,
-                tag_list,
-                article.created_at,
-                article.updated_at,
-                exists(select 1 from article_favorite where article_id = article.article_id and user_id = $1) "favorited!",
-                (select count(*) from article_favorite fav where fav.article_id = article.article_id) "favorites_count!",
-                author.username author_username,
-                author.bio author_bio,
-                author.image author_image,
-                exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
-            from article
-            inner join "user" author using (user_id)
-            where slug = $2
+            update article
+            set view_count = view_count + 1
+            where slug = $1 and (published or user_id = $2)
+            returning article_id
         "#,
-        maybe_claims.as_ref().map(|claims| claims.sub),
-        slug
+        slug,
+        viewer
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    let article = select_articles!(
+        r#"
+            where article_id = $2
+        "#,
+        viewer,
+        article_id
     )
     .fetch_optional(&state.db)
     .await?
     .ok_or(Error::NotFound)?
-    .into_article();
+    .into_article(&state.config);
 
-    Ok(Json(ArticleBody { article }))
+    let mut body = ArticleBody { article };
+
+    if render_html {
+        let article = &mut body.article;
+        article.body_html = Some(state.html_cache.render(
+            article.id,
+            article.updated_at,
+            &article.body,
+        ));
+    }
+
+    // Left out of the validator, or no read could ever be answered with 304.
+    let view_count = std::mem::take(&mut body.article.view_count);
+    let etag = ETag::of(&body)?;
+    body.article.view_count = view_count;
+
+    Ok(etag::conditional(&headers, etag, Json(body)))
+}
+
+/// How the favorite endpoints address an article: by slug, or by id for clients that already hold
+/// one and should not be caught out by a slug changing with the title.
+enum ArticleKey {
+    Slug(String),
+    Id(Uuid),
 }
 
+impl ArticleKey {
+    fn slug(&self) -> Option<&str> {
+        match self {
+            Self::Slug(slug) => Some(slug),
+            Self::Id(_) => None,
+        }
+    }
+
+    fn id(&self) -> Option<Uuid> {
+        match self {
+            Self::Slug(_) => None,
+            Self::Id(article_id) => Some(*article_id),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/articles/{slug}/favorite",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    responses(
+        (status = 200, description = "Favorited article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn favorite_article(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(slug): Path<String>,
+    mut tx: Tx,
+) -> Result<Json<ArticleBody>> {
+    favorite(&mut tx, &state.config, &claims, ArticleKey::Slug(slug)).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/articles/id/{article_id}/favorite",
+    tag = "articles",
+    security(("token" = [])),
+    params(("article_id" = Uuid, Path, description = "Article id")),
+    responses(
+        (status = 200, description = "Favorited article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn favorite_article_by_id(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(article_id): Path<Uuid>,
+    mut tx: Tx,
+) -> Result<Json<ArticleBody>> {
+    favorite(&mut tx, &state.config, &claims, ArticleKey::Id(article_id)).await
+}
+
+async fn favorite(
+    conn: &mut PgConnection,
+    config: &Config,
+    claims: &Claims,
+    key: ArticleKey,
 ) -> Result<Json<ArticleBody>> {
     let article_id = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
             with selected_article as (
-                select article_id from article where slug = $1
+                select article_id from article
+                where (slug = $1 or article_id = $3) and (published or user_id = $2)
             ),
             inserted_favorite as (
                 insert into article_favorite(article_id, user_id)
@@ -346,55 +1076,112 @@ async fn favorite_article(
             )
             select article_id from selected_article
         "#,
-        slug,
-        claims.sub
+        key.slug(),
+        claims.sub,
+        key.id()
     )
-    .fetch_optional(&state.db)
-    .await?
+    .fetch_optional(&mut *conn)
+    .await
+    .on_constraint("article_favorite_user_id_fkey", |_| Error::Unauthorized)?
     .ok_or(Error::NotFound)?;
 
-    Ok(Json(ArticleBody {
-        article: article_by_id(&state.db, claims.sub, article_id).await?,
-    }))
+    let article = article_by_id(conn, config, claims.sub, article_id).await?;
+
+    Ok(Json(ArticleBody { article }))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/articles/{slug}/favorite",
+    tag = "articles",
+    security(("token" = [])),
+    params(("slug" = String, Path, description = "Article slug")),
+    responses(
+        (status = 200, description = "Unfavorited article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn unfavorite_article(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(slug): Path<String>,
+    mut tx: Tx,
+) -> Result<Json<ArticleBody>> {
+    unfavorite(&mut tx, &state.config, &claims, ArticleKey::Slug(slug)).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/articles/id/{article_id}/favorite",
+    tag = "articles",
+    security(("token" = [])),
+    params(("article_id" = Uuid, Path, description = "Article id")),
+    responses(
+        (status = 200, description = "Unfavorited article", body = ArticleBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn unfavorite_article_by_id(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(article_id): Path<Uuid>,
+    mut tx: Tx,
+) -> Result<Json<ArticleBody>> {
+    unfavorite(&mut tx, &state.config, &claims, ArticleKey::Id(article_id)).await
+}
+
+async fn unfavorite(
+    conn: &mut PgConnection,
+    config: &Config,
+    claims: &Claims,
+    key: ArticleKey,
 ) -> Result<Json<ArticleBody>> {
     let article_id = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
             with selected_article as (
-                select article_id from article where slug = $1
+                select article_id from article
+                where (slug = $1 or article_id = $3) and (published or user_id = $2)
             ),
-            inserted_favorite as (
+            deleted_favorite as (
                 delete from article_favorite
                 where article_id = (select article_id from selected_article)
                 and user_id = $2
             )
             select article_id from selected_article
         "#,
-        slug,
-        claims.sub
+        key.slug(),
+        claims.sub,
+        key.id()
     )
-    .fetch_optional(&state.db)
+    .fetch_optional(&mut *conn)
     .await?
     .ok_or(Error::NotFound)?;
 
-    Ok(Json(ArticleBody {
-        article: article_by_id(&state.db, claims.sub, article_id).await?,
-    }))
+    let article = article_by_id(conn, config, claims.sub, article_id).await?;
+
+    Ok(Json(ArticleBody { article }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tags",
+    tag = "articles",
+    responses((status = 200, description = "All tags in use", body = TagsBody))
+)]
 async fn get_tags(state: State<AppState>) -> Result<Json<TagsBody>> {
     let tags = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
-            select distinct tag "tag!"
-            from article, unnest (article.tag_list) tags(tag)
-            order by tag;
+            select name "tag!"
+            from tag
+            where exists(
+                select 1 from article_tag inner join article using (article_id)
+                where article_tag.tag_id = tag.tag_id and article.published
+            )
+            order by name;
         "#
     )
     .fetch_all(&state.db)
@@ -403,32 +1190,137 @@ async fn get_tags(state: State<AppState>) -> Result<Json<TagsBody>> {
     Ok(Json(TagsBody { tags }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/tags/counts",
+    tag = "articles",
+    responses((status = 200, description = "Tags with their article counts, most used first", body = TagCountsBody))
+)]
+async fn get_tag_counts(state: State<AppState>) -> Result<Json<TagCountsBody>> {
+    let tags = sqlx::query_as!(
+        TagCount,
+        // language=PostgreSQL
+        r#"
+            select name "tag!", count(*) "count!"
+            from tag
+            inner join article_tag using (tag_id)
+            inner join article using (article_id)
+            where article.published
+            group by name
+            order by count(*) desc, name;
+        "#
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(TagCountsBody { tags }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/tags/trending",
+    tag = "articles",
+    params(TrendingTagsQuery),
+    responses((status = 200, description = "Most used tags among recent articles", body = TagCountsBody))
+)]
+async fn get_trending_tags(
+    state: State<AppState>,
+    Query(query): Query<TrendingTagsQuery>,
+) -> Result<Json<TagCountsBody>> {
+    let tags = sqlx::query_as!(
+        TagCount,
+        // language=PostgreSQL
+        r#"
+            select name "tag!", count(*) "count!"
+            from tag
+            inner join article_tag using (tag_id)
+            inner join article using (article_id)
+            where article.published and article.created_at > now() - make_interval(days => $1)
+            group by name
+            order by count(*) desc, name
+            limit $2;
+        "#,
+        query
+            .days
+            .unwrap_or(DEFAULT_TRENDING_DAYS)
+            .clamp(1, MAX_TRENDING_DAYS),
+        query
+            .limit
+            .unwrap_or(DEFAULT_TRENDING_LIMIT)
+            .clamp(1, MAX_TRENDING_LIMIT)
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(TagCountsBody { tags }))
+}
+
+/// Authors writing about `tag`, those with the most published articles under it first. A tag no
+/// published article uses answers with an empty list rather than 404, as the listings do.
+#[utoipa::path(
+    get,
+    path = "/api/tags/{tag}/authors",
+    tag = "articles",
+    params(("tag" = String, Path, description = "Tag name")),
+    responses((status = 200, description = "Authors with their article counts for the tag", body = TagAuthorsBody))
+)]
+async fn get_tag_authors(
+    state: State<AppState>,
+    Path(tag): Path<String>,
+) -> Result<Json<TagAuthorsBody>> {
+    let authors = sqlx::query_as!(
+        TagAuthor,
+        // language=PostgreSQL
+        r#"
+            select author.username, count(*) "count!"
+            from article
+            inner join "user" author using (user_id)
+            inner join article_tag using (article_id)
+            inner join tag using (tag_id)
+            where article.published and tag.name = $1
+            group by author.username
+            order by count(*) desc, author.username;
+        "#,
+        normalize_tag(&tag)
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(TagAuthorsBody { authors }))
+}
+
+/// Resolves `slug` to its article id, failing with 404 if there is no such article and 403 if
+/// `user_id` is not its author.
+async fn owned_article_id(
+    e: impl Executor<'_, Database = Postgres>,
+    slug: &str,
+    user_id: Uuid,
+) -> Result<Uuid> {
+    let article = sqlx::query!(
+        // language=PostgreSQL
+        r#"select article_id, user_id from article where slug = $1"#,
+        slug
+    )
+    .fetch_optional(e)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    if article.user_id != user_id {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(article.article_id)
+}
+
 async fn article_by_id(
     e: impl Executor<'_, Database = Postgres>,
+    config: &Config,
     user_id: Uuid,
     article_id: Uuid,
 ) -> Result<Article> {
-    let article = sqlx::query_as!(
-        ArticleFromQuery,
-        // language=PostgreSQL
+    let article = select_articles!(
         r#"
-            select
-                slug,
-                title,
-                description,
-                body,
-                tag_list,
-                article.created_at,
-                article.updated_at,
-                exists(select 1 from article_favorite where article_id = article.article_id and user_id = $1) "favorited!",
-                (select count(*) from article_favorite fav where fav.article_id = article.article_id) "favorites_count!",
-                author.username author_username,
-                author.bio author_bio,
-                author.image author_image,
-                exists(select 1 from follow where followed_user_id = author.user_id and following_user_id = $1) "following_author!"
-            from article
-            inner join "user" author using (user_id)
-            where article_id = $2
+            where article_id = $2 and (article.published or article.user_id = $1)
         "#,
         user_id,
         article_id
@@ -436,28 +1328,205 @@ async fn article_by_id(
     .fetch_optional(e)
     .await?
     .ok_or(Error::NotFound)?
-    .into_article();
+    .into_article(config);
 
     Ok(article)
 }
 
+/// Replaces the tags of `article_id` with `tags`, creating any that do not exist yet.
+///
+/// `tags` should already have gone through [`normalize_tags`].
+pub(crate) async fn set_article_tags(
+    e: impl Executor<'_, Database = Postgres>,
+    article_id: Uuid,
+    tags: &[String],
+) -> Result<()> {
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            with wanted_tag as (
+                -- The no-op update makes existing tags come back from `returning` too.
+                insert into tag (name)
+                select * from unnest($2::text[])
+                on conflict (name) do update set name = excluded.name
+                returning tag_id
+            ),
+            removed_tag as (
+                delete from article_tag
+                where article_id = $1 and tag_id not in (select tag_id from wanted_tag)
+            )
+            insert into article_tag (article_id, tag_id)
+            select $1, tag_id from wanted_tag
+            on conflict do nothing
+        "#,
+        article_id,
+        tags
+    )
+    .execute(e)
+    .await?;
+
+    Ok(())
+}
+
+const SLUG_ATTEMPTS: usize = 5;
+
+/// Moves `article_id` to the first free slug for `title`, the same way a new article gets one.
+async fn regenerate_article_slug(
+    conn: &mut PgConnection,
+    article_id: Uuid,
+    title: &str,
+) -> Result<()> {
+    for slug in slug_candidates(title) {
+        let updated = sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"
+                update article
+                set slug = $2
+                where article_id = $1
+                and not exists(select 1 from article taken where taken.slug = $2 and taken.article_id <> $1)
+                returning 1 "updated!"
+            "#,
+            article_id,
+            slug
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .on_constraint("article_slug_key", |_| {
+            Error::unprocessable_entity([("slug", format!("duplicate article slug: {slug}"))])
+        })?;
+
+        if updated.is_some() {
+            return Ok(());
+        }
+    }
+
+    Err(Error::unprocessable_entity([(
+        "slug",
+        "could not generate a unique article slug",
+    )]))
+}
+
+fn slug_candidates(title: &str) -> impl Iterator<Item = String> {
+    let slug = slugify(title);
+
+    std::iter::once(slug.clone())
+        .chain((1..SLUG_ATTEMPTS).map(move |_| format!("{}-{}", slug, short_token())))
+}
+
 fn slugify(title: &str) -> String {
-    title
+    let title = if title.is_ascii() {
+        Cow::Borrowed(title)
+    } else {
+        // deunicode spells emoji and other symbols out as words ("🦀" becomes "crab"), which makes
+        // for odd slugs, so only letters and digits are transliterated.
+        let letters: String = title
+            .chars()
+            .map(|c| {
+                if c.is_ascii() || c.is_alphanumeric() {
+                    c
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        Cow::Owned(deunicode::deunicode(&letters))
+    };
+
+    let slug = title
         .to_ascii_lowercase()
         .chars()
-        .map(|c| match c {
-            'a'..='z' | '0'..='9' => c,
-            'á' | 'à' | 'ä' | 'â' => 'a',
-            'é' | 'è' | 'ë' | 'ê' => 'e',
-            'í' | 'ì' | 'ï' | 'î' => 'i',
-            'ó' | 'ò' | 'ö' | 'ô' => 'o',
-            'ú' | 'ù' | 'ü' | 'û' => 'u',
-            'ñ' => 'n',
-            '\'' | '\\' => '\0',
-            _ => ' ',
+        .filter_map(|c| match c {
+            'a'..='z' | '0'..='9' => Some(c),
+            '\'' | '\\' => None,
+            _ => Some(' '),
         })
         .collect::<String>()
         .split_whitespace()
         .collect::<Vec<_>>()
-        .join("-")
+        .join("-");
+
+    if slug.is_empty() {
+        short_token()
+    } else {
+        slug
+    }
+}
+
+fn short_token() -> String {
+    Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugify_transliterates_cyrillic() {
+        assert_eq!(slugify("Привет мир"), "privet-mir");
+    }
+
+    #[test]
+    fn slugify_transliterates_cjk() {
+        let slug = slugify("你好世界");
+
+        assert!(!slug.is_empty());
+        assert!(slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'));
+    }
+
+    #[test]
+    fn slugify_falls_back_to_a_token_for_emoji_only_titles() {
+        let slug = slugify("🦀🚀");
+
+        assert_eq!(slug.len(), 8);
+        assert!(slug.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn normalize_tags_merges_spellings_of_one_tag() {
+        assert_eq!(
+            normalize_tags(strings(&["Rust", " rust ", "rust"])),
+            strings(&["rust"])
+        );
+    }
+
+    #[test]
+    fn normalize_tags_collapses_internal_whitespace() {
+        assert_eq!(
+            normalize_tags(strings(&["Web  Dev", "web\tdev", "Async\n Rust"])),
+            strings(&["async rust", "web dev"])
+        );
+    }
+
+    #[test]
+    fn normalize_tags_turns_blank_tags_into_one_empty_tag() {
+        // Left for validation to reject, as `tag_errors` does with "can't contain blank tags".
+        assert_eq!(normalize_tags(strings(&["", "   ", "\t"])), strings(&[""]));
+    }
+
+    fn words(count: usize) -> String {
+        vec!["word"; count].join(" ")
+    }
+
+    #[test]
+    fn reading_time_is_at_least_one_minute() {
+        assert_eq!(reading_time_minutes("", 200), 1);
+        assert_eq!(reading_time_minutes(&words(199), 200), 1);
+    }
+
+    #[test]
+    fn reading_time_counts_whole_minutes() {
+        assert_eq!(reading_time_minutes(&words(400), 200), 2);
+    }
+
+    #[test]
+    fn reading_time_follows_words_per_minute() {
+        assert_eq!(reading_time_minutes(&words(400), 100), 4);
+        assert_eq!(reading_time_minutes(&words(400), 400), 1);
+    }
 }
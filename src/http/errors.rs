@@ -1,18 +1,26 @@
 use axum::{
-    http::{header::WWW_AUTHENTICATE, HeaderMap, HeaderValue, StatusCode},
+    extract::rejection::{JsonRejection, PathRejection},
+    http::{
+        header::{RETRY_AFTER, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
     response::{IntoResponse, Response},
     Json,
 };
+use axum_extra::extract::QueryRejection;
 use serde::Serialize;
 use sqlx::error::DatabaseError;
 use std::{borrow::Cow, collections::HashMap};
 use thiserror::Error;
-use tracing::log::error;
+use tracing::log::{error, warn};
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("authentication required")]
     Unauthorized,
+    #[error("session token is {reason}")]
+    InvalidToken { reason: &'static str },
     #[error("user may not perform that action")]
     Forbidden,
     #[error("request path not found")]
@@ -21,6 +29,14 @@ pub enum Error {
     UnprocessableEntity {
         errors: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
     },
+    #[error("{message}")]
+    Rejection {
+        status: StatusCode,
+        field: &'static str,
+        message: String,
+    },
+    #[error("too many requests, retry in {retry_after} seconds")]
+    TooManyRequests { retry_after: u64 },
     #[error("an error occurred with the database")]
     Sqlx(#[from] sqlx::Error),
     #[error("an internal server error occurred")]
@@ -47,37 +63,95 @@ impl Error {
 
     fn status_code(&self) -> StatusCode {
         match self {
-            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Unauthorized | Self::InvalidToken { .. } => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::UnprocessableEntity { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Rejection { status, .. } => *status,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Sqlx(e) if is_statement_timeout(e) || is_connection_lost(e) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
             Self::Sqlx(_) | Self::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
 
+/// Postgres cancels statements running past `statement_timeout` with `query_canceled`.
+fn is_statement_timeout(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(e) if e.code().as_deref() == Some("57014"))
+}
+
+/// Failures to reach the database at all, as opposed to errors in what was sent to it.
+///
+/// Besides pool and socket errors this covers the SQLSTATEs Postgres sends when it refuses or
+/// drops connections: class 08, `too_many_connections` and the `57P0x` shutdown codes.
+fn is_connection_lost(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::PoolTimedOut
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::Io(_)
+        | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+            code.starts_with("08") || code.starts_with("57P0") || code == "53300"
+        }),
+        _ => false,
+    }
+}
+
+/// Seconds clients are asked to wait before retrying when the database is unreachable or too slow.
+const DATABASE_RETRY_AFTER_SECONDS: u64 = 5;
+
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
             Self::UnprocessableEntity { errors } => {
-                #[derive(Serialize)]
-                struct Errors {
-                    errors: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
-                }
-
                 return (StatusCode::UNPROCESSABLE_ENTITY, Json(Errors { errors })).into_response();
             }
+            Self::Rejection {
+                status,
+                field,
+                message,
+            } => {
+                let errors = HashMap::from([(field.into(), vec![message.into()])]);
+
+                return (status, Json(Errors { errors })).into_response();
+            }
             Self::Unauthorized => {
+                return unauthorized("authorization", self.to_string());
+            }
+            Self::InvalidToken { reason } => {
+                return unauthorized("token", reason.to_owned());
+            }
+            Self::TooManyRequests { retry_after } => {
+                let errors = HashMap::from([("request".into(), vec![self.to_string().into()])]);
+
                 return (
                     self.status_code(),
-                    [(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"))]
-                        .into_iter()
-                        .collect::<HeaderMap>(),
-                    self.to_string(),
+                    [(RETRY_AFTER, HeaderValue::from(retry_after))],
+                    Json(Errors { errors }),
                 )
                     .into_response();
             }
 
+            Self::Sqlx(ref e) if is_statement_timeout(e) || is_connection_lost(e) => {
+                let message = if is_statement_timeout(e) {
+                    warn!("database statement timed out: {:?}", e);
+                    "timed out"
+                } else {
+                    warn!("database unavailable: {:?}", e);
+                    "is unavailable"
+                };
+
+                let errors = HashMap::from([("database".into(), vec![message.into()])]);
+
+                return (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    [(RETRY_AFTER, HeaderValue::from(DATABASE_RETRY_AFTER_SECONDS))],
+                    Json(Errors { errors }),
+                )
+                    .into_response();
+            }
             Self::Sqlx(ref e) => {
                 error!("SQLx error: {:?}", e);
             }
@@ -93,6 +167,54 @@ impl IntoResponse for Error {
     }
 }
 
+/// 401s name the scheme tokens are issued under, so clients know what to send back.
+fn unauthorized(field: &'static str, message: String) -> Response {
+    let errors = HashMap::from([(field.into(), vec![message.into()])]);
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, HeaderValue::from_static("Token"))],
+        Json(Errors { errors }),
+    )
+        .into_response()
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Errors {
+    #[schema(value_type = HashMap<String, Vec<String>>)]
+    errors: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
+}
+
+impl From<JsonRejection> for Error {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::Rejection {
+            status: rejection.status(),
+            field: "body",
+            message: rejection.body_text(),
+        }
+    }
+}
+
+impl From<PathRejection> for Error {
+    fn from(rejection: PathRejection) -> Self {
+        Self::Rejection {
+            status: rejection.status(),
+            field: "path",
+            message: rejection.body_text(),
+        }
+    }
+}
+
+impl From<QueryRejection> for Error {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::Rejection {
+            status: rejection.status(),
+            field: "query",
+            message: rejection.body_text(),
+        }
+    }
+}
+
 pub trait ResultExt<T> {
     fn on_constraint(
         self,
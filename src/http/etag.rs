@@ -0,0 +1,47 @@
+use axum::http::header::{ETAG, IF_NONE_MATCH};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Strong validator over the serialized form of whatever identifies a representation.
+///
+/// Hashing the representation rather than using `updated_at` keeps viewer-relative fields such as
+/// `favorited` from being served stale.
+pub(crate) struct ETag(String);
+
+impl ETag {
+    pub(crate) fn of<T: Serialize>(value: &T) -> anyhow::Result<Self> {
+        let digest = Sha256::digest(serde_json::to_vec(value)?);
+        let hex: String = digest[..16]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        Ok(Self(format!("\"{hex}\"")))
+    }
+
+    /// `If-None-Match` uses weak comparison, so a `W/` prefix on the client's copy is ignored.
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.0)
+    }
+}
+
+/// Answers 304 without a body when the client already holds `etag`, and `response` otherwise.
+pub(crate) fn conditional(
+    headers: &HeaderMap,
+    etag: ETag,
+    response: impl IntoResponse,
+) -> Response {
+    if etag.matches(headers) {
+        (StatusCode::NOT_MODIFIED, [(ETAG, etag.0)]).into_response()
+    } else {
+        ([(ETAG, etag.0)], response).into_response()
+    }
+}
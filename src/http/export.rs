@@ -0,0 +1,258 @@
+use super::auth::{self, Claims};
+use super::{AppState, Error, Result};
+use crate::http::extractor::Json;
+use axum::extract::State;
+use axum::routing::get;
+use axum::{middleware, Extension, Router};
+use serde::Serialize;
+use time::OffsetDateTime;
+use utoipa::{OpenApi, ToSchema};
+
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new().route(
+        "/api/user/export",
+        get(export_user).route_layer(middleware::from_fn_with_state(state, auth::auth)),
+    )
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(export_user))]
+pub(super) struct ExportApi;
+
+#[derive(Serialize, ToSchema)]
+struct ExportBody {
+    export: UserExport,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct UserExport {
+    user: ExportedUser,
+    /// Drafts included
+    articles: Vec<ExportedArticle>,
+    /// Comments the user wrote, on anyone's articles
+    comments: Vec<ExportedComment>,
+    favorites: Vec<ExportedFavorite>,
+    /// Users this user follows
+    following: Vec<ExportedFollow>,
+    /// Users following this user
+    followers: Vec<ExportedFollow>,
+    /// Set when a list was cut off at `EXPORT_MAX_ITEMS`
+    truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportedUser {
+    email: String,
+    username: String,
+    bio: String,
+    image: Option<String>,
+    created_at: OffsetDateTime,
+    updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportedArticle {
+    slug: String,
+    title: String,
+    description: String,
+    body: String,
+    tag_list: Vec<String>,
+    published: bool,
+    created_at: OffsetDateTime,
+    updated_at: OffsetDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportedComment {
+    id: i64,
+    article_slug: String,
+    body: String,
+    created_at: OffsetDateTime,
+    updated_at: OffsetDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFavorite {
+    article_slug: String,
+    created_at: OffsetDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFollow {
+    username: String,
+    created_at: OffsetDateTime,
+}
+
+/// Everything stored about the current user, for data portability.
+///
+/// Read from a single snapshot so that the lists agree with each other. Each list holds at most
+/// `EXPORT_MAX_ITEMS` entries, oldest first.
+#[utoipa::path(
+    get,
+    path = "/api/user/export",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "The current user's data", body = ExportBody),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+async fn export_user(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<ExportBody>> {
+    // One more than the cap, to tell a full list from a cut off one.
+    let limit = state.config.export_max_items + 1;
+
+    let mut tx = state.db.begin().await?;
+
+    sqlx::query!("set transaction isolation level repeatable read, read only")
+        .execute(&mut *tx)
+        .await?;
+
+    let user = sqlx::query_as!(
+        ExportedUser,
+        // language=PostgreSQL
+        r#"
+            select email, username, bio, image, created_at, updated_at
+            from "user" where user_id = $1
+        "#,
+        claims.sub
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::Unauthorized)?;
+
+    let mut articles = sqlx::query_as!(
+        ExportedArticle,
+        // language=PostgreSQL
+        r#"
+            select
+                slug,
+                title,
+                description,
+                body,
+                array(
+                    select name from article_tag inner join tag using (tag_id)
+                    where article_tag.article_id = article.article_id
+                    order by name
+                ) "tag_list!",
+                published,
+                created_at,
+                updated_at
+            from article
+            where user_id = $1
+            order by created_at, slug
+            limit $2
+        "#,
+        claims.sub,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut comments = sqlx::query_as!(
+        ExportedComment,
+        // language=PostgreSQL
+        r#"
+            select comment_id id, article.slug article_slug, comment.body, comment.created_at, comment.updated_at
+            from article_comment comment
+            inner join article using (article_id)
+            where comment.user_id = $1
+            order by comment.created_at, comment_id
+            limit $2
+        "#,
+        claims.sub,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut favorites = sqlx::query_as!(
+        ExportedFavorite,
+        // language=PostgreSQL
+        r#"
+            select article.slug article_slug, favorite.created_at
+            from article_favorite favorite
+            inner join article using (article_id)
+            where favorite.user_id = $1
+            order by favorite.created_at, article.slug
+            limit $2
+        "#,
+        claims.sub,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut following = sqlx::query_as!(
+        ExportedFollow,
+        // language=PostgreSQL
+        r#"
+            select username, follow.created_at
+            from follow
+            inner join "user" on user_id = followed_user_id
+            where following_user_id = $1
+            order by follow.created_at, username
+            limit $2
+        "#,
+        claims.sub,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut followers = sqlx::query_as!(
+        ExportedFollow,
+        // language=PostgreSQL
+        r#"
+            select username, follow.created_at
+            from follow
+            inner join "user" on user_id = following_user_id
+            where followed_user_id = $1
+            order by follow.created_at, username
+            limit $2
+        "#,
+        claims.sub,
+        limit
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let max_items = state.config.export_max_items as usize;
+    let truncated = [
+        articles.len(),
+        comments.len(),
+        favorites.len(),
+        following.len(),
+        followers.len(),
+    ]
+    .into_iter()
+    .any(|len| len > max_items);
+
+    articles.truncate(max_items);
+    comments.truncate(max_items);
+    favorites.truncate(max_items);
+    following.truncate(max_items);
+    followers.truncate(max_items);
+
+    Ok(Json(ExportBody {
+        export: UserExport {
+            user,
+            articles,
+            comments,
+            favorites,
+            following,
+            followers,
+            truncated,
+        },
+    }))
+}
@@ -0,0 +1,23 @@
+use super::Error;
+use axum::extract::{FromRequest, FromRequestParts};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(Error))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(Error))]
+pub struct Path<T>(pub T);
+
+/// Unlike `axum::extract::Query`, collects repeated keys (`?tag=a&tag=b`) into a `Vec`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum_extra::extract::Query), rejection(Error))]
+pub struct Query<T>(pub T);
@@ -0,0 +1,95 @@
+use super::AppState;
+use crate::http::extractor::Json;
+use crate::MIGRATOR;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/api/health", get(health))
+        .route("/api/ready", get(ready))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(health, ready))]
+pub(super) struct HealthApi;
+
+#[derive(Serialize, ToSchema)]
+struct HealthBody {
+    status: &'static str,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ReadyBody {
+    status: &'static str,
+    pool: PoolStats,
+}
+
+/// Connections currently held by the pool, and how many of those are idle.
+#[derive(Serialize, ToSchema)]
+struct PoolStats {
+    size: u32,
+    idle: usize,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "health",
+    responses((status = 200, description = "Process is up", body = HealthBody))
+)]
+async fn health() -> Json<HealthBody> {
+    Json(HealthBody { status: "ok" })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Database is reachable and fully migrated", body = ReadyBody),
+        (status = 503, description = "Database is unreachable or behind the migrations this build expects", body = ReadyBody),
+    )
+)]
+async fn ready(state: State<AppState>) -> (StatusCode, Json<ReadyBody>) {
+    // Not checked at build time: the table only exists once the migrator has run.
+    let applied: Result<Option<i64>, _> = sqlx::query_scalar(
+        // language=PostgreSQL
+        "select max(version) from _sqlx_migrations where success",
+    )
+    .fetch_one(&state.db)
+    .await;
+    let expected = MIGRATOR.iter().map(|migration| migration.version).max();
+
+    let status = match applied {
+        Ok(applied) if applied >= expected => "ok",
+        Ok(applied) => {
+            tracing::warn!(
+                "readiness check failed: schema is at migration {applied:?}, expected {expected:?}"
+            );
+            "migrations pending"
+        }
+        Err(e) => {
+            tracing::warn!("readiness check failed: {:?}", e);
+            "unavailable"
+        }
+    };
+
+    let body = ReadyBody {
+        status,
+        pool: PoolStats {
+            size: state.db.size(),
+            idle: state.db.num_idle(),
+        },
+    };
+
+    if status == "ok" {
+        (StatusCode::OK, Json(body))
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, Json(body))
+    }
+}
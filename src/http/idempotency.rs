@@ -0,0 +1,188 @@
+use super::auth::Claims;
+use super::{AppState, Error, Result};
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderName, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+
+const IDEMPOTENCY_KEY_HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+
+const MAX_KEY_LENGTH: usize = 255;
+
+/// Replays the first successful response for a repeated `Idempotency-Key`, scoped per user.
+///
+/// Layered inside [`super::auth::auth`]. Requests without the header pass straight through, and
+/// failed attempts release the key so that the client can retry with it.
+pub async fn idempotency(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .ok_or_else(|| {
+            Error::unprocessable_entity([(
+                "Idempotency-Key",
+                "must be between 1 and 255 visible ASCII characters",
+            )])
+        })?
+        .to_owned();
+    let path = request.uri().path().to_owned();
+
+    // A statement of its own: an insert sharing its snapshot would still conflict with the
+    // expired row it replaces, and leave the key unclaimable.
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            delete from idempotency_key
+            where user_id = $1 and created_at < now() - make_interval(secs => $2)
+        "#,
+        claims.sub,
+        state.config.idempotency_key_ttl_seconds as f64
+    )
+    .execute(&state.db)
+    .await?;
+
+    let claimed = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            insert into idempotency_key (user_id, key, request_path)
+            values ($1, $2, $3)
+            on conflict do nothing
+            returning 1 "claimed!"
+        "#,
+        claims.sub,
+        key,
+        path
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .is_some();
+
+    if !claimed {
+        return replay(&state, claims, &key, &path).await;
+    }
+
+    let response = next.run(request).await;
+
+    if !response.status().is_success() {
+        release(&state, &claims, &key).await?;
+
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            release(&state, &claims, &key).await?;
+            return Err(anyhow::anyhow!("failed to buffer response body: {e}").into());
+        }
+    };
+
+    let stored = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            update idempotency_key
+            set status_code = $3, response_body = $4, response_location = $5
+            where user_id = $1 and key = $2
+        "#,
+        claims.sub,
+        key,
+        parts.status.as_u16() as i16,
+        &body[..],
+        parts
+            .headers
+            .get(header::LOCATION)
+            .and_then(|location| location.to_str().ok())
+    )
+    .execute(&state.db)
+    .await;
+
+    // Left claimed without a response, the key would answer 409 until it expired.
+    if let Err(e) = stored {
+        release(&state, &claims, &key).await?;
+        return Err(e.into());
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Frees `key` for the client to retry with.
+async fn release(state: &AppState, claims: &Claims, key: &str) -> Result<()> {
+    sqlx::query!(
+        // language=PostgreSQL
+        "delete from idempotency_key where user_id = $1 and key = $2",
+        claims.sub,
+        key
+    )
+    .execute(&state.db)
+    .await?;
+
+    Ok(())
+}
+
+async fn replay(state: &AppState, claims: Claims, key: &str, path: &str) -> Result<Response> {
+    let stored = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            select request_path, status_code, response_body, response_location
+            from idempotency_key
+            where user_id = $1 and key = $2
+        "#,
+        claims.sub,
+        key
+    )
+    .fetch_optional(&state.db)
+    .await?
+    // Released by a failed first attempt in the meantime.
+    .ok_or_else(|| conflict("is being used by a request that has not finished"))?;
+
+    if stored.request_path != path {
+        return Err(Error::unprocessable_entity([(
+            "Idempotency-Key",
+            "was already used for a different request",
+        )]));
+    }
+
+    let (Some(status_code), Some(body)) = (stored.status_code, stored.response_body) else {
+        return Err(conflict("is being used by a request that has not finished"));
+    };
+
+    let status = StatusCode::from_u16(status_code as u16)
+        .map_err(|e| anyhow::anyhow!("invalid stored status code: {e}"))?;
+
+    let mut response = (
+        status,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )],
+        body,
+    )
+        .into_response();
+
+    if let Some(location) = stored.response_location {
+        let location = HeaderValue::try_from(location)
+            .map_err(|e| anyhow::anyhow!("invalid stored location: {e}"))?;
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+
+    Ok(response)
+}
+
+fn conflict(message: &str) -> Error {
+    Error::Rejection {
+        status: StatusCode::CONFLICT,
+        field: "Idempotency-Key",
+        message: message.to_owned(),
+    }
+}
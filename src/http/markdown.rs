@@ -0,0 +1,60 @@
+use pulldown_cmark::{html, Options, Parser};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Rendered bodies are dropped all at once past this many articles, rather than tracking which
+/// were used least recently.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Renders markdown to HTML that is safe to embed, with scripts, event handlers and the like
+/// stripped.
+pub(crate) fn render_html(markdown: &str) -> String {
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, Options::all()));
+
+    ammonia::clean(&unsafe_html)
+}
+
+/// Rendered article bodies, keyed by article and reused until the article's `updated_at` moves.
+pub(crate) struct HtmlCache {
+    entries: Mutex<HashMap<Uuid, (OffsetDateTime, String)>>,
+}
+
+impl HtmlCache {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        article_id: Uuid,
+        updated_at: OffsetDateTime,
+        markdown: &str,
+    ) -> String {
+        if let Some((rendered_at, html)) = self
+            .entries
+            .lock()
+            .expect("html cache mutex poisoned")
+            .get(&article_id)
+        {
+            if *rendered_at == updated_at {
+                return html.clone();
+            }
+        }
+
+        // Rendered outside the lock so that one long body does not hold up every other read.
+        let html = render_html(markdown);
+
+        let mut entries = self.entries.lock().expect("html cache mutex poisoned");
+        if entries.len() >= MAX_ENTRIES {
+            entries.clear();
+        }
+        entries.insert(article_id, (updated_at, html.clone()));
+
+        html
+    }
+}
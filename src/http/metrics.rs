@@ -0,0 +1,73 @@
+use super::AppState;
+use anyhow::Context;
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::{Duration, Instant};
+
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Installs the global Prometheus recorder and spawns the task that drains its histograms.
+pub(crate) fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(REQUEST_DURATION.to_string()),
+            &[
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ],
+        )
+        .context("invalid histogram buckets")?
+        .install_recorder()
+        .context("failed to install metrics recorder")?;
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/metrics", get(render))
+}
+
+async fn render(state: State<AppState>) -> String {
+    metrics::gauge!("db_pool_connections").set(state.db.size() as f64);
+    metrics::gauge!("db_pool_idle_connections").set(state.db.num_idle() as f64);
+
+    state.metrics.render()
+}
+
+/// Must be applied with `route_layer` so the matched route template is available as the
+/// `path` label; labelling by the raw URI would create a series per slug.
+pub(crate) async fn track(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| req.uri().path().to_owned());
+
+    let response = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("path", path),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!(REQUEST_DURATION, &labels).record(start.elapsed().as_secs_f64());
+
+    response
+}
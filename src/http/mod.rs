@@ -1,17 +1,49 @@
+mod admin;
 mod article;
 mod auth;
 mod errors;
+mod etag;
+mod export;
+mod extractor;
+mod health;
+mod idempotency;
+mod markdown;
+mod metrics;
+mod openapi;
+mod password_reset;
 mod profiles;
+mod rate_limit;
+mod transaction;
 mod users;
 
 use crate::config::Config;
 use anyhow::Context;
-use axum::Router;
+use argon2::Argon2;
+pub(crate) use article::set_article_tags;
+use auth::JwtKeys;
+use axum::extract::DefaultBodyLimit;
+use axum::http::header::{ETAG, LOCATION};
+use axum::http::{HeaderName, HeaderValue, Request};
+use axum::{middleware, Router};
+use axum_server::tls_rustls::RustlsConfig;
 pub use errors::Error;
+use markdown::HtmlCache;
+use metrics_exporter_prometheus::PrometheusHandle;
+use rate_limit::RateLimiter;
 use sqlx::PgPool;
+use std::future::Future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tower_http::trace::TraceLayer;
+use tokio::sync::Notify;
+use tower_http::cors::{AllowHeaders, Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::timeout::TimeoutLayer;
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tracing::Level;
+pub(crate) use users::{hash_password, password_hasher};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -19,28 +51,330 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 struct AppState {
     config: Arc<Config>,
     db: PgPool,
+    jwt_keys: Arc<JwtKeys>,
+    login_limiter: Arc<RateLimiter>,
+    argon2: Argon2<'static>,
+    metrics: PrometheusHandle,
+    html_cache: Arc<HtmlCache>,
 }
 
-pub async fn serve(config: Config, db: PgPool) -> anyhow::Result<()> {
-    let listener = TcpListener::bind("0.0.0.0:8080").await?;
+/// Taken from the client when present, generated otherwise.
+const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Serves the API until `shutdown` resolves; `main` passes [`shutdown_signal`].
+pub async fn serve(
+    config: Config,
+    db: PgPool,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let jwt_keys = JwtKeys::from_config(&config)?;
+    let argon2 = password_hasher(&config)?;
+    let cors = cors_layer(&config)?;
+    let tls = tls_config(&config).await?;
+    let metrics = metrics::install_recorder()?;
+
+    let listener = TcpListener::bind(config.listen_addr)
+        .await
+        .with_context(|| format!("failed to bind to {}", config.listen_addr))?;
+    tracing::info!("listening on {}", listener.local_addr()?);
+
+    let login_limiter = RateLimiter::new(
+        config.login_rate_limit_attempts,
+        Duration::from_secs(config.login_rate_limit_window_seconds),
+    );
+
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_seconds);
+    let body_limit = RequestBodyLimitLayer::new(config.request_body_limit_bytes);
+    let timeout = TimeoutLayer::new(Duration::from_secs(config.request_timeout_seconds));
+
     let state = AppState {
         config: Arc::new(config),
-        db,
+        db: db.clone(),
+        jwt_keys: Arc::new(jwt_keys),
+        login_limiter: Arc::new(login_limiter),
+        argon2,
+        metrics,
+        html_cache: Arc::new(HtmlCache::new()),
     };
 
-    let app = api_router(state.config.clone())
+    if let Some(addr) = state.config.metrics_listen_addr {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to bind metrics listener to {addr}"))?;
+        tracing::info!("serving metrics on {}", listener.local_addr()?);
+
+        let metrics_app = metrics::router().with_state(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, metrics_app).await {
+                tracing::error!("error running metrics server: {e}");
+            }
+        });
+    }
+
+    let app = api_router(state.clone())
+        .route_layer(middleware::from_fn(metrics::track))
         .with_state(state)
-        .layer(TraceLayer::new_for_http());
+        // `RequestBodyLimitLayer` replaces the extractors' built-in 2 MB default.
+        .layer(DefaultBodyLimit::disable())
+        .layer(body_limit)
+        .layer(timeout)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<_>| {
+                    let request_id = request
+                        .headers()
+                        .get(REQUEST_ID_HEADER)
+                        .and_then(|id| id.to_str().ok())
+                        .unwrap_or_default();
+                    tracing::info_span!(
+                        "request",
+                        request_id,
+                        method = %request.method(),
+                        path = %request.uri().path(),
+                    )
+                })
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        // Echoes the id on every response, including errors produced by the layers above.
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
+        .layer(cors);
 
-    axum::serve(listener, app)
-        .await
-        .context("error running HTTP server")?;
+    run_until(listener, app, tls, shutdown, shutdown_timeout).await?;
+
+    db.close().await;
+
+    Ok(())
+}
+
+/// Serves `app` until `shutdown` resolves, then gives in-flight requests `shutdown_timeout` to
+/// finish before returning without them.
+async fn run_until(
+    listener: TcpListener,
+    app: Router,
+    tls: Option<RustlsConfig>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    shutdown_timeout: Duration,
+) -> anyhow::Result<()> {
+    let shutdown_started = Arc::new(Notify::new());
+    let shutdown = {
+        let shutdown_started = shutdown_started.clone();
+        async move {
+            shutdown.await;
+            tracing::info!("shutting down, draining in-flight requests");
+            shutdown_started.notify_one();
+        }
+    };
+    let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let server = async move {
+        let Some(tls) = tls else {
+            return axum::serve(listener, make_service)
+                .with_graceful_shutdown(shutdown)
+                .await;
+        };
+
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                shutdown.await;
+                handle.graceful_shutdown(None);
+            }
+        });
+
+        axum_server::from_tcp_rustls(listener.into_std()?, tls)
+            .handle(handle)
+            .serve(make_service)
+            .await
+    };
+
+    tokio::select! {
+        res = server => res.context("error running HTTP server")?,
+        _ = async {
+            shutdown_started.notified().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        } => {
+            tracing::warn!("requests still in flight after {shutdown_timeout:?}, closing anyway");
+        }
+    }
 
     Ok(())
 }
 
-fn api_router(state: Arc<Config>) -> Router<AppState> {
-    users::router(state.clone())
+/// Resolves on the first SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+fn api_router(state: AppState) -> Router<AppState> {
+    let router = users::router(state.clone())
+        .merge(password_reset::router())
+        .merge(export::router(state.clone()))
         .merge(profiles::router(state.clone()))
-        .merge(article::router(state))
+        .merge(article::router(state.clone()))
+        .merge(admin::router(state.clone()))
+        .merge(health::router())
+        .merge(openapi::router());
+
+    // With a dedicated metrics address, `/metrics` is only served there.
+    if state.config.metrics_listen_addr.is_some() {
+        router
+    } else {
+        router.merge(metrics::router())
+    }
+}
+
+/// Loads the certificate and key up front, so that a bad path fails startup rather than the first
+/// handshake.
+async fn tls_config(config: &Config) -> anyhow::Result<Option<RustlsConfig>> {
+    let (Some(cert), Some(key)) = (&config.tls_cert_path, &config.tls_key_path) else {
+        return Ok(None);
+    };
+
+    // Chosen explicitly rather than left to whichever rustls features dependencies happen to enable.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    let tls = RustlsConfig::from_pem_file(cert, key)
+        .await
+        .with_context(|| {
+            format!(
+                "failed to load TLS certificate {} and key {}",
+                cert.display(),
+                key.display()
+            )
+        })?;
+
+    Ok(Some(tls))
+}
+
+fn cors_layer(config: &Config) -> anyhow::Result<CorsLayer> {
+    let cors = CorsLayer::new()
+        .allow_methods(Any)
+        .allow_headers(AllowHeaders::mirror_request())
+        .expose_headers([REQUEST_ID_HEADER, ETAG, LOCATION]);
+
+    if config.cors_allowed_origins.is_empty() {
+        tracing::warn!("no CORS allowed origins configured, allowing any origin");
+        return Ok(cors.allow_origin(Any));
+    }
+
+    let origins = config
+        .cors_allowed_origins
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin))
+        .collect::<Result<Vec<_>, _>>()
+        .context("invalid CORS allowed origin")?;
+
+    Ok(cors.allow_origin(origins))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    async fn get_request(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn stops_when_the_signal_fires() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let (signal, shutdown) = oneshot::channel::<()>();
+        let server = tokio::spawn(run_until(
+            listener,
+            app,
+            None,
+            async move {
+                shutdown.await.ok();
+            },
+            Duration::from_secs(30),
+        ));
+
+        assert!(get_request(addr, "/").await.starts_with("HTTP/1.1 200"));
+
+        signal.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server kept running after the signal")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn drops_requests_still_in_flight_after_the_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let started = Arc::new(Notify::new());
+        let finished = Arc::new(AtomicBool::new(false));
+        let app = Router::new().route(
+            "/slow",
+            get({
+                let started = started.clone();
+                let finished = finished.clone();
+                move || async move {
+                    started.notify_one();
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    finished.store(true, Ordering::SeqCst);
+                    "done"
+                }
+            }),
+        );
+        let (signal, shutdown) = oneshot::channel::<()>();
+        let server = tokio::spawn(run_until(
+            listener,
+            app,
+            None,
+            async move {
+                shutdown.await.ok();
+            },
+            Duration::from_millis(100),
+        ));
+
+        let client = tokio::spawn(get_request(addr, "/slow"));
+        started.notified().await;
+
+        signal.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server waited for the slow request")
+            .unwrap()
+            .unwrap();
+
+        assert!(!finished.load(Ordering::SeqCst));
+        assert!(!client.is_finished());
+    }
 }
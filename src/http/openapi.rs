@@ -0,0 +1,47 @@
+use super::errors::Errors;
+use super::{admin, article, export, health, password_reset, profiles, users, AppState};
+use axum::Router;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Conduit", description = "RealWorld backend API"),
+    components(schemas(Errors)),
+    modifiers(&TokenAuth)
+)]
+struct ApiDoc;
+
+/// Registers the `token` scheme that authenticated routes list under `security`.
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "`Token <jwt>` or `Bearer <jwt>`",
+            ))),
+        );
+    }
+}
+
+/// Serves the generated spec at `/api/openapi.json` and Swagger UI at `/swagger-ui`.
+pub fn router() -> Router<AppState> {
+    let doc = ApiDoc::openapi()
+        .merge_from(users::UsersApi::openapi())
+        .merge_from(password_reset::PasswordResetApi::openapi())
+        .merge_from(export::ExportApi::openapi())
+        .merge_from(profiles::ProfilesApi::openapi())
+        .merge_from(article::ArticlesApi::openapi())
+        .merge_from(article::CommentsApi::openapi())
+        .merge_from(admin::AdminApi::openapi())
+        .merge_from(health::HealthApi::openapi());
+
+    SwaggerUi::new("/swagger-ui")
+        .url("/api/openapi.json", doc)
+        .into()
+}
@@ -0,0 +1,152 @@
+use super::users::{hash_password, validate_credentials, UserBody};
+use super::{AppState, Error, Result};
+use crate::http::errors::Errors;
+use crate::http::extractor::Json;
+use axum::extract::State;
+use axum::routing::post;
+use axum::Router;
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use utoipa::{OpenApi, ToSchema};
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/user/password-reset-request",
+            post(request_password_reset),
+        )
+        .route("/api/user/password-reset", post(reset_password))
+}
+
+#[derive(OpenApi)]
+#[openapi(paths(request_password_reset, reset_password))]
+pub(super) struct PasswordResetApi;
+
+const TOKEN_BYTES: usize = 32;
+
+#[derive(Deserialize, ToSchema)]
+struct PasswordResetRequest {
+    email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PasswordReset {
+    token: String,
+    password: String,
+}
+
+/// Always answers 200 so the response doesn't reveal whether the email is registered.
+///
+/// There is no mail delivery yet. With `LOG_PASSWORD_RESET_TOKENS` set the token is written to the
+/// server log, which is meant for development; otherwise only the request is logged.
+#[utoipa::path(
+    post,
+    path = "/api/user/password-reset-request",
+    tag = "users",
+    request_body = UserBody<PasswordResetRequest>,
+    responses((status = 200, description = "Reset token issued if the email is registered"))
+)]
+async fn request_password_reset(
+    state: State<AppState>,
+    Json(req): Json<UserBody<PasswordResetRequest>>,
+) -> Result<()> {
+    let mut token = [0u8; TOKEN_BYTES];
+    rand::thread_rng().fill_bytes(&mut token);
+    let token = to_hex(&token);
+
+    let user_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"
+            insert into password_reset (token_hash, user_id, expires_at)
+            select $1, user_id, now() + make_interval(secs => $3)
+            from "user" where email = $2
+            returning user_id
+        "#,
+        &hash_token(&token)[..],
+        req.user.email,
+        state.config.password_reset_token_seconds as f64
+    )
+    .fetch_optional(&state.db)
+    .await?;
+
+    if let Some(user_id) = user_id {
+        if state.config.log_password_reset_tokens {
+            tracing::warn!(%user_id, "password reset requested, token: {token}");
+        } else {
+            tracing::info!(%user_id, "password reset requested");
+        }
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/password-reset",
+    tag = "users",
+    request_body = UserBody<PasswordReset>,
+    responses(
+        (status = 200, description = "Password updated"),
+        (status = 422, description = "Invalid, expired or already used token", body = Errors),
+    )
+)]
+async fn reset_password(
+    state: State<AppState>,
+    Json(req): Json<UserBody<PasswordReset>>,
+) -> Result<()> {
+    let errors = validate_credentials(None, Some(&req.user.password));
+    if !errors.is_empty() {
+        return Err(Error::unprocessable_entity(errors));
+    }
+
+    let mut tx = state.db.begin().await?;
+
+    // Deleting up front consumes the token whether or not it is still valid.
+    let reset = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            delete from password_reset
+            where token_hash = $1
+            returning user_id, expires_at > now() "valid!"
+        "#,
+        &hash_token(&req.user.token)[..]
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::unprocessable_entity([("token", "is invalid")]))?;
+
+    if !reset.valid {
+        tx.commit().await?;
+        return Err(Error::unprocessable_entity([("token", "has expired")]));
+    }
+
+    let password_hash = hash_password(&state.argon2, req.user.password).await?;
+
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            with other_resets as (
+                delete from password_reset where user_id = $1
+            )
+            update "user" set password_hash = $2 where user_id = $1
+        "#,
+        reset.user_id,
+        password_hash
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Only a digest is stored, so a leaked table can't be used to reset passwords.
+fn hash_token(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
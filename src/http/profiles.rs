@@ -1,15 +1,19 @@
 use super::{auth, AppState, Error, Result};
-use crate::config::Config;
 use crate::http::auth::Claims;
-use crate::http::errors::ResultExt;
-use axum::extract::{Path, State};
+use crate::http::errors::{Errors, ResultExt};
+use crate::http::extractor::{Json, Path, Query};
+use axum::extract::State;
 use axum::routing::{get, post};
-use axum::{middleware, Extension, Json, Router};
-use serde::Serialize;
-use std::sync::Arc;
+use axum::{middleware, Extension, Router};
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Postgres};
+use time::OffsetDateTime;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use uuid::Uuid;
 
-pub fn router(state: Arc<Config>) -> Router<AppState> {
+pub fn router(state: AppState) -> Router<AppState> {
     Router::new()
+        .route("/api/profiles/search", get(search_usernames))
         .route(
             "/api/profiles/{username}",
             get(get_user_profile).route_layer(middleware::from_fn_with_state(
@@ -17,6 +21,13 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
                 auth::maybe_auth,
             )),
         )
+        .route(
+            "/api/profiles/{username}/followers",
+            get(get_followers).route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::maybe_auth,
+            )),
+        )
         .route(
             "/api/profiles/{username}/follow",
             post(follow_user)
@@ -25,106 +36,362 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
         )
 }
 
-#[derive(Serialize)]
+#[derive(OpenApi)]
+#[openapi(paths(
+    search_usernames,
+    get_user_profile,
+    get_followers,
+    follow_user,
+    unfollow_user
+))]
+pub(super) struct ProfilesApi;
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+struct ProfilesQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[derive(Deserialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(default)]
+struct UsernameSearchQuery {
+    /// Start of the username, matched case-insensitively
+    prefix: String,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct UsernamesBody {
+    usernames: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
 struct ProfileBody {
     profile: Profile,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+struct MultipleProfilesBody {
+    profiles: Vec<Profile>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 pub struct Profile {
     pub username: String,
     pub bio: String,
     pub image: Option<String>,
     pub following: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub followers_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub following_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<OffsetDateTime>,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/profiles/{username}",
+    tag = "profiles",
+    security((), ("token" = [])),
+    params(("username" = String, Path, description = "Profile username")),
+    responses(
+        (status = 200, description = "Profile", body = ProfileBody),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn get_user_profile(
     state: State<AppState>,
     Extension(maybe_claims): Extension<Option<Claims>>,
     Path(username): Path<String>,
 ) -> Result<Json<ProfileBody>> {
-    let profile = sqlx::query_as!(
-        Profile,
+    let profile = profile_by_username(
+        &state.db,
+        &username,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+    )
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(Json(ProfileBody { profile }))
+}
+
+/// For autocompletion: usernames starting with `prefix`, in alphabetical order.
+#[utoipa::path(
+    get,
+    path = "/api/profiles/search",
+    tag = "profiles",
+    params(UsernameSearchQuery),
+    responses(
+        (status = 200, description = "Matching usernames", body = UsernamesBody),
+        (status = 422, description = "Missing `prefix`", body = Errors),
+    )
+)]
+async fn search_usernames(
+    state: State<AppState>,
+    Query(query): Query<UsernameSearchQuery>,
+) -> Result<Json<UsernamesBody>> {
+    if query.prefix.is_empty() {
+        return Err(Error::unprocessable_entity([("prefix", "can't be blank")]));
+    }
+
+    // Taken literally, not as a LIKE pattern.
+    let prefix = query
+        .prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    // Matches the expression of the `user_username_prefix` index.
+    let usernames = sqlx::query_scalar!(
         // language=PostgreSQL
         r#"
-            select
-                username,
-                bio,
-                image,
-                exists(
-                    select 1 from follow
-                    where followed_user_id = "user".user_id and following_user_id = $2
-                ) "following!"
+            select username
             from "user"
-            where username = $1
+            where lower(username) collate "C" like lower($1) || '%'
+            order by lower(username) collate "C", username
+            limit $2
         "#,
-        username,
-        maybe_claims.as_ref().map(|claims| claims.sub)
+        prefix,
+        state.config.page_size(query.limit)
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(UsernamesBody { usernames }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/profiles/{username}/followers",
+    tag = "profiles",
+    security((), ("token" = [])),
+    params(
+        ("username" = String, Path, description = "Profile username"),
+        ProfilesQuery
+    ),
+    responses(
+        (status = 200, description = "Followers, most recent first", body = MultipleProfilesBody),
+        (status = 404, description = "Not found"),
+    )
+)]
+async fn get_followers(
+    state: State<AppState>,
+    Extension(maybe_claims): Extension<Option<Claims>>,
+    Path(username): Path<String>,
+    Query(query): Query<ProfilesQuery>,
+) -> Result<Json<MultipleProfilesBody>> {
+    let user_id = sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select user_id from "user" where username = $1"#,
+        username
     )
     .fetch_optional(&state.db)
     .await?
     .ok_or(Error::NotFound)?;
 
-    Ok(Json(ProfileBody { profile }))
+    let profiles = sqlx::query_as!(
+        Profile,
+        // language=PostgreSQL
+        r#"
+            select
+                follower.username,
+                follower.bio,
+                follower.image,
+                exists(
+                    select 1 from follow
+                    where followed_user_id = follower.user_id and following_user_id = $2
+                ) "following!",
+                null::int8 "followers_count?",
+                null::int8 "following_count?",
+                null::timestamptz "created_at?"
+            from follow
+            inner join "user" follower on follower.user_id = follow.following_user_id
+            where follow.followed_user_id = $1
+            order by follow.created_at desc, follower.username
+            limit $3
+            offset $4
+        "#,
+        user_id,
+        maybe_claims.as_ref().map(|claims| claims.sub),
+        state.config.page_size(query.limit),
+        query.offset.unwrap_or(0).max(0)
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(MultipleProfilesBody { profiles }))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/profiles/{username}/follow",
+    tag = "profiles",
+    security(("token" = [])),
+    params(("username" = String, Path, description = "Profile username")),
+    responses(
+        (status = 200, description = "Followed profile", body = ProfileBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Cannot follow yourself"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn follow_user(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(username): Path<String>,
 ) -> Result<Json<ProfileBody>> {
-    let profile = sqlx::query_as!(
-        Profile,
+    let mut tx = state.db.begin().await?;
+
+    let user_id = user_id_by_username(&mut *tx, &username).await?;
+
+    // Locking the follower's row serializes their concurrent follows, so two of them cannot both
+    // squeeze in under the limit.
+    let follows = sqlx::query!(
         // language=PostgreSQL
         r#"
-            with selected_user as (
-                select user_id, username, bio, image
-                from "user" where username = $1
+            select
+                (select count(*) from follow where following_user_id = user_id) "count!",
+                exists(
+                    select 1 from follow where following_user_id = user_id and followed_user_id = $2
+                ) "already_following!"
+            from "user"
+            where user_id = $1
+            for no key update
+        "#,
+        claims.sub,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(Error::Unauthorized)?;
+
+    if !follows.already_following && follows.count >= state.config.max_follows_per_user {
+        return Err(Error::unprocessable_entity([(
+            "following",
+            format!(
+                "is too long (maximum is {} users)",
+                state.config.max_follows_per_user
             ),
-            insert_follow as (
-                insert into follow (following_user_id, followed_user_id)
-                    select $2, user_id
-                    from selected_user
-                    on conflict do nothing
-            )
-            select su.username, su.bio, su.image, true "following!"
-            from selected_user su;
+        )]));
+    }
+
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            insert into follow (following_user_id, followed_user_id)
+            values ($1, $2)
+            on conflict do nothing
         "#,
-        username,
-        claims.sub
+        claims.sub,
+        user_id
     )
-    .fetch_one(&state.db)
+    .execute(&mut *tx)
     .await
-    .on_constraint("user_cannot_follow_self", |_| Error::Forbidden)?;
+    .on_constraint("user_cannot_follow_self", |_| Error::Forbidden)
+    .on_constraint("follow_following_user_id_fkey", |_| Error::Unauthorized)?;
+
+    let profile = profile_by_username(&mut *tx, &username, Some(claims.sub))
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    tx.commit().await?;
 
     Ok(Json(ProfileBody { profile }))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/profiles/{username}/follow",
+    tag = "profiles",
+    security(("token" = [])),
+    params(("username" = String, Path, description = "Profile username")),
+    responses(
+        (status = 200, description = "Unfollowed profile", body = ProfileBody),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 403, description = "Cannot unfollow yourself"),
+        (status = 404, description = "Not found"),
+    )
+)]
 async fn unfollow_user(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
     Path(username): Path<String>,
 ) -> Result<Json<ProfileBody>> {
+    let mut tx = state.db.begin().await?;
+
+    let user_id = user_id_by_username(&mut *tx, &username).await?;
+
+    // Mirrors `user_cannot_follow_self` on the follow side.
+    if user_id == claims.sub {
+        return Err(Error::Forbidden);
+    }
+
+    sqlx::query!(
+        // language=PostgreSQL
+        "delete from follow where following_user_id = $1 and followed_user_id = $2",
+        claims.sub,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let profile = profile_by_username(&mut *tx, &username, Some(claims.sub))
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    tx.commit().await?;
+
+    Ok(Json(ProfileBody { profile }))
+}
+
+/// The full profile shape, shared by every endpoint answering with a single [`Profile`].
+async fn profile_by_username(
+    e: impl Executor<'_, Database = Postgres>,
+    username: &str,
+    viewer: Option<Uuid>,
+) -> Result<Option<Profile>> {
     let profile = sqlx::query_as!(
         Profile,
         // language=PostgreSQL
         r#"
-            with selected_user as (
-                select user_id, username, bio, image
-                from "user" where username = $1
-            ),
-            delet_follow as (
-                delete from follow where following_user_id = $2
-                    and followed_user_id = (SELECT user_id FROM selected_user)
-            )
-            select su.username, su.bio, su.image, false "following!"
-            from selected_user su;
+            select
+                username,
+                bio,
+                image,
+                exists(
+                    select 1 from follow
+                    where followed_user_id = "user".user_id and following_user_id = $2
+                ) "following!",
+                (select count(*) from follow where followed_user_id = "user".user_id) "followers_count?",
+                (select count(*) from follow where following_user_id = "user".user_id) "following_count?",
+                created_at "created_at?"
+            from "user"
+            where username = $1
         "#,
         username,
-        claims.sub
+        viewer
     )
-    .fetch_one(&state.db)
+    .fetch_optional(e)
     .await?;
 
-    Ok(Json(ProfileBody { profile }))
+    Ok(profile)
+}
+
+async fn user_id_by_username(
+    e: impl Executor<'_, Database = Postgres>,
+    username: &str,
+) -> Result<Uuid> {
+    sqlx::query_scalar!(
+        // language=PostgreSQL
+        r#"select user_id from "user" where username = $1"#,
+        username
+    )
+    .fetch_optional(e)
+    .await?
+    .ok_or(Error::NotFound)
 }
@@ -0,0 +1,75 @@
+use super::{Error, Result};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Fixed-window attempt counter keyed by client address.
+///
+/// Keys whose window has elapsed are swept at most once per window, so the map only ever holds
+/// clients seen during roughly the last two windows.
+pub(crate) struct RateLimiter {
+    max_attempts: u32,
+    window: Duration,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    windows: HashMap<IpAddr, Window>,
+    last_sweep: Instant,
+}
+
+struct Window {
+    started: Instant,
+    attempts: u32,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(max_attempts: u32, window: Duration) -> Self {
+        Self {
+            max_attempts,
+            window,
+            inner: Mutex::new(Inner {
+                windows: HashMap::new(),
+                last_sweep: Instant::now(),
+            }),
+        }
+    }
+
+    /// Records an attempt from `addr`, failing with 429 once the window's budget is spent.
+    pub(crate) fn check(&self, addr: IpAddr) -> Result<()> {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().expect("rate limiter mutex poisoned");
+
+        if now.duration_since(inner.last_sweep) >= self.window {
+            inner
+                .windows
+                .retain(|_, window| now.duration_since(window.started) < self.window);
+            inner.last_sweep = now;
+        }
+
+        let window = inner.windows.entry(addr).or_insert(Window {
+            started: now,
+            attempts: 0,
+        });
+
+        let elapsed = now.duration_since(window.started);
+        if elapsed >= self.window {
+            window.started = now;
+            window.attempts = 0;
+        }
+
+        if window.attempts >= self.max_attempts {
+            let remaining = self
+                .window
+                .saturating_sub(now.duration_since(window.started));
+            return Err(Error::TooManyRequests {
+                retry_after: remaining.as_secs_f64().ceil().max(1.0) as u64,
+            });
+        }
+
+        window.attempts += 1;
+
+        Ok(())
+    }
+}
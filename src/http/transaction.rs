@@ -0,0 +1,76 @@
+use super::{AppState, Error, Result};
+use axum::extract::{FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use sqlx::{PgConnection, Postgres, Transaction};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+type Slot = Arc<Mutex<Option<Transaction<'static, Postgres>>>>;
+
+/// Wraps the request in a database transaction, committed when the handler answers with a 2xx and
+/// rolled back otherwise.
+///
+/// The transaction is only begun once a handler extracts [`Tx`], so requests rejected before
+/// reaching one never take a connection for it.
+pub async fn transaction(mut request: Request, next: Next) -> Result<Response> {
+    let slot = Slot::default();
+    request.extensions_mut().insert(slot.clone());
+
+    let response = next.run(request).await;
+
+    // The handler has returned, and with it dropped its `Tx`.
+    let Some(tx) = slot.lock().await.take() else {
+        return Ok(response);
+    };
+
+    if response.status().is_success() {
+        tx.commit().await?;
+    } else {
+        tx.rollback().await?;
+    }
+
+    Ok(response)
+}
+
+/// The request's transaction, for handlers on routes layered with [`transaction`].
+pub(crate) struct Tx(OwnedMutexGuard<Option<Transaction<'static, Postgres>>>);
+
+impl FromRequestParts<AppState> for Tx {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let slot = parts
+            .extensions
+            .get::<Slot>()
+            .ok_or_else(|| {
+                anyhow::anyhow!("Tx extracted on a route without the transaction layer")
+            })?
+            .clone();
+        let mut tx = slot
+            .try_lock_owned()
+            .map_err(|_| anyhow::anyhow!("Tx extracted twice in the same request"))?;
+
+        if tx.is_none() {
+            *tx = Some(state.db.begin().await?);
+        }
+
+        Ok(Self(tx))
+    }
+}
+
+impl Deref for Tx {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &PgConnection {
+        self.0.as_ref().expect("transaction is set on extraction")
+    }
+}
+
+impl DerefMut for Tx {
+    fn deref_mut(&mut self) -> &mut PgConnection {
+        self.0.as_mut().expect("transaction is set on extraction")
+    }
+}
@@ -1,17 +1,28 @@
 use super::auth::Claims;
+use super::etag::{self, ETag};
 use super::{auth, AppState, Error, Result};
 use crate::config::Config;
-use crate::http::errors::ResultExt;
+use crate::http::errors::{Errors, ResultExt};
+use crate::http::extractor::Json;
 use anyhow::Context;
 use argon2::password_hash::SaltString;
-use argon2::{Argon2, PasswordHash};
-use axum::extract::State;
+use argon2::{Algorithm, Argon2, Params, PasswordHash, Version};
+use axum::extract::{ConnectInfo, State};
+use axum::http::header::LOCATION;
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::Response;
 use axum::routing::{get, post};
-use axum::{middleware, Extension, Json, Router};
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use axum::{middleware, Extension, Router};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{Executor, Postgres};
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use time::OffsetDateTime;
+use url::Url;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
 
-pub fn router(state: Arc<Config>) -> Router<AppState> {
+pub fn router(state: AppState) -> Router<AppState> {
     Router::new()
         .route("/api/users", post(create_user))
         .route("/api/users/login", post(login_user))
@@ -19,56 +30,154 @@ pub fn router(state: Arc<Config>) -> Router<AppState> {
             "/api/user",
             get(get_current_user)
                 .put(update_user)
-                .route_layer(middleware::from_fn_with_state(state, auth::auth)),
+                .delete(delete_user)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/user/refresh",
+            post(refresh_token)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/user/stats",
+            get(get_user_stats)
+                .route_layer(middleware::from_fn_with_state(state.clone(), auth::auth)),
+        )
+        .route(
+            "/api/user/logout",
+            post(logout_user).route_layer(middleware::from_fn_with_state(state, auth::auth)),
         )
 }
 
-#[derive(Serialize, Deserialize)]
-struct UserBody<T> {
-    user: T,
+#[derive(OpenApi)]
+#[openapi(paths(
+    create_user,
+    login_user,
+    get_current_user,
+    refresh_token,
+    get_user_stats,
+    logout_user,
+    delete_user,
+    update_user
+))]
+pub(super) struct UsersApi;
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(super) struct UserBody<T> {
+    pub(super) user: T,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct NewUser {
     username: String,
     email: String,
     password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct LoginUser {
     email: String,
     password: String,
 }
 
-#[derive(Deserialize, Default, PartialEq, Eq)]
+#[derive(Deserialize, Default, PartialEq, Eq, ToSchema)]
 #[serde(default)]
 struct UpdateUser {
     email: Option<String>,
     username: Option<String>,
     password: Option<String>,
-    bio: Option<String>,
-    image: Option<String>,
+    /// `null` resets the bio to empty; omitting the field leaves it as is.
+    #[serde(deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    bio: Option<Option<String>>,
+    /// `null` removes the image; omitting the field leaves it as is.
+    #[serde(deserialize_with = "present")]
+    #[schema(value_type = Option<String>)]
+    image: Option<Option<String>>,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field, which `#[serde(default)]`
+/// leaves as `None`.
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, ToSchema)]
+struct StatsBody {
+    stats: UserStats,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+struct UserStats {
+    articles_count: i64,
+    /// Favorites received on the user's articles
+    favorites_count: i64,
+    /// Comments received on the user's articles
+    comments_count: i64,
+    followers_count: i64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
 struct User {
     email: String,
     token: String,
     username: String,
     bio: String,
     image: Option<String>,
+    created_at: OffsetDateTime,
+    updated_at: Option<OffsetDateTime>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/users",
+    tag = "users",
+    request_body = UserBody<NewUser>,
+    responses(
+        (status = 201, description = "Registered user", body = UserBody<User>,
+            headers(("Location" = String, description = "URL of the current user"))),
+        (status = 422, description = "Validation failed", body = Errors),
+    )
+)]
 async fn create_user(
     state: State<AppState>,
-    Json(req): Json<UserBody<NewUser>>,
-) -> Result<Json<UserBody<User>>> {
-    let password_hash = hash_password(req.user.password).await?;
+    Json(mut req): Json<UserBody<NewUser>>,
+) -> Result<(
+    StatusCode,
+    [(HeaderName, &'static str); 1],
+    Json<UserBody<User>>,
+)> {
+    req.user.username = req.user.username.trim().to_owned();
+
+    let mut errors = validate_credentials(Some(&req.user.email), Some(&req.user.password));
+    errors.extend(username_error(&req.user.username));
+    errors.extend(
+        taken_fields(
+            &state.db,
+            Some(&req.user.username),
+            Some(&req.user.email),
+            None,
+        )
+        .await?,
+    );
+    if !errors.is_empty() {
+        return Err(Error::unprocessable_entity(errors));
+    }
 
-    let user_id = sqlx::query_scalar!(
+    let password_hash = hash_password(&state.argon2, req.user.password).await?;
+
+    let user = sqlx::query!(
         // language=PostgreSQL
-        "insert into \"user\" (username, email, password_hash) values ($1, $2, $3) returning user_id",
+        r#"
+            insert into "user" (username, email, password_hash) values ($1, $2, $3)
+            returning user_id, created_at
+        "#,
         req.user.username,
         req.user.email,
         password_hash
@@ -82,25 +191,46 @@ async fn create_user(
         Error::unprocessable_entity([("email", "email taken")])
     })?;
 
-    Ok(Json(UserBody {
-        user: User {
-            email: req.user.email,
-            token: Claims::with_sub_to_jwt(user_id, &state),
-            username: req.user.username,
-            bio: "".to_string(),
-            image: None,
-        },
-    }))
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, "/api/user")],
+        Json(UserBody {
+            user: User {
+                email: req.user.email,
+                token: Claims::with_sub_to_jwt(user.user_id, &state)?,
+                username: req.user.username,
+                bio: "".to_string(),
+                image: None,
+                created_at: user.created_at,
+                updated_at: None,
+            },
+        }),
+    ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/users/login",
+    tag = "users",
+    request_body = UserBody<LoginUser>,
+    responses(
+        (status = 200, description = "Authenticated user", body = UserBody<User>),
+        (status = 401, description = "Wrong password"),
+        (status = 422, description = "Validation failed", body = Errors),
+        (status = 429, description = "Too many login attempts"),
+    )
+)]
 async fn login_user(
     state: State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
     Json(req): Json<UserBody<LoginUser>>,
 ) -> Result<Json<UserBody<User>>> {
+    state.login_limiter.check(addr.ip())?;
+
     let user = sqlx::query!(
         // language=PostgreSQL
         r#"
-            select user_id, email, username, bio, image, password_hash
+            select user_id, email, username, bio, image, password_hash, created_at, updated_at
             from "user" where email = $1
         "#,
         req.user.email
@@ -109,55 +239,246 @@ async fn login_user(
     .await?
     .ok_or(Error::unprocessable_entity([("email", "does not exist")]))?;
 
-    verify_password(req.user.password, user.password_hash).await?;
+    let outdated_hash = needs_rehash(&state.argon2, &user.password_hash);
+    verify_password(&state.argon2, req.user.password.clone(), user.password_hash).await?;
+
+    if outdated_hash {
+        if let Err(e) =
+            rehash_password(&state.db, &state.argon2, user.user_id, req.user.password).await
+        {
+            tracing::warn!("failed to upgrade password hash: {e:?}");
+        }
+    }
 
     Ok(Json(UserBody {
         user: User {
             email: user.email,
-            token: Claims::with_sub_to_jwt(user.user_id, &state),
+            token: Claims::with_sub_to_jwt(user.user_id, &state)?,
             username: user.username,
             bio: user.bio,
-            image: None,
+            image: user.image,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
         },
     }))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/user",
+    tag = "users",
+    security(("token" = [])),
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previously fetched copy")
+    ),
+    responses(
+        (status = 200, description = "Current user", body = UserBody<User>,
+            headers(("ETag" = String))),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
 async fn get_current_user(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
-) -> Result<Json<UserBody<User>>> {
+    headers: HeaderMap,
+) -> Result<Response> {
+    let user = current_user(&state, &claims).await?;
+
+    // The token is reissued on every request, so it is left out of the tag.
+    let etag = ETag::of(&(
+        &user.email,
+        &user.username,
+        &user.bio,
+        &user.image,
+        user.created_at,
+        user.updated_at,
+    ))?;
+
+    Ok(etag::conditional(&headers, etag, Json(UserBody { user })))
+}
+
+async fn current_user(state: &AppState, claims: &Claims) -> Result<User> {
     let user = sqlx::query!(
         // language=PostgreSQL
         r#"
-            select email, username, bio, image from "user" where user_id = $1
+            select email, username, bio, image, created_at, updated_at
+            from "user" where user_id = $1
         "#,
         claims.sub
     )
     .fetch_one(&state.db)
     .await?;
 
+    Ok(User {
+        email: user.email,
+        token: Claims::with_sub_to_jwt(claims.sub, state)?,
+        username: user.username,
+        bio: user.bio,
+        image: user.image,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/refresh",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Current user with a fresh token", body = UserBody<User>),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+async fn refresh_token(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<UserBody<User>>> {
     Ok(Json(UserBody {
-        user: User {
-            email: user.email,
-            token: Claims::with_sub_to_jwt(claims.sub, &state),
-            username: user.username,
-            bio: user.bio,
-            image: user.image,
-        },
+        user: current_user(&state, &claims).await?,
     }))
 }
 
+/// Favorites and comments are counted across all of the user's articles, drafts included.
+#[utoipa::path(
+    get,
+    path = "/api/user/stats",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Totals for the current user's articles", body = StatsBody),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+async fn get_user_stats(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<StatsBody>> {
+    let stats = sqlx::query_as!(
+        UserStats,
+        // language=PostgreSQL
+        r#"
+            select
+                (select count(*) from article where article.user_id = $1) "articles_count!",
+                (
+                    select count(*) from article_favorite
+                    inner join article using (article_id)
+                    where article.user_id = $1
+                ) "favorites_count!",
+                (
+                    select count(*) from article_comment
+                    inner join article using (article_id)
+                    where article.user_id = $1
+                ) "comments_count!",
+                (select count(*) from follow where followed_user_id = $1) "followers_count!"
+            from "user"
+            where user_id = $1
+        "#,
+        claims.sub
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or(Error::Unauthorized)?;
+
+    Ok(Json(StatsBody { stats }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/logout",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 200, description = "Token revoked"),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+async fn logout_user(state: State<AppState>, Extension(claims): Extension<Claims>) -> Result<()> {
+    claims.revoke(&state.db).await
+}
+
+/// Articles, comments, favorites and follows all cascade from the `"user"` row.
+#[utoipa::path(
+    delete,
+    path = "/api/user",
+    tag = "users",
+    security(("token" = [])),
+    responses(
+        (status = 204, description = "Account deleted"),
+        (status = 401, description = "Missing or invalid token"),
+    )
+)]
+async fn delete_user(
+    state: State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<StatusCode> {
+    let mut tx = state.db.begin().await?;
+
+    claims.revoke(&mut *tx).await?;
+
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"delete from "user" where user_id = $1"#,
+        claims.sub
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/user",
+    tag = "users",
+    security(("token" = [])),
+    request_body = UserBody<UpdateUser>,
+    responses(
+        (status = 200, description = "Updated user", body = UserBody<User>),
+        (status = 401, description = "Missing or invalid token"),
+        (status = 422, description = "Validation failed", body = Errors),
+    )
+)]
 async fn update_user(
     state: State<AppState>,
     Extension(claims): Extension<Claims>,
-    Json(req): Json<UserBody<UpdateUser>>,
+    Json(mut req): Json<UserBody<UpdateUser>>,
 ) -> Result<Json<UserBody<User>>> {
     if req.user == UpdateUser::default() {
-        return get_current_user(state, Extension(claims)).await;
+        return Ok(Json(UserBody {
+            user: current_user(&state, &claims).await?,
+        }));
+    }
+
+    if let Some(username) = &mut req.user.username {
+        *username = username.trim().to_owned();
+    }
+
+    let mut errors = validate_credentials(req.user.email.as_deref(), req.user.password.as_deref());
+    if let Some(username) = &req.user.username {
+        errors.extend(username_error(username));
+    }
+    if let Some(Some(image)) = &req.user.image {
+        errors.extend(validate_image_url(image, &state.config.image_allowed_hosts));
+    }
+    errors.extend(
+        taken_fields(
+            &state.db,
+            req.user.username.as_deref(),
+            req.user.email.as_deref(),
+            Some(claims.sub),
+        )
+        .await?,
+    );
+    if !errors.is_empty() {
+        return Err(Error::unprocessable_entity(errors));
     }
 
     let password_hash = if let Some(password) = req.user.password {
-        Some(hash_password(password).await?)
+        Some(hash_password(&state.argon2, password).await?)
     } else {
         None
     };
@@ -169,16 +490,18 @@ async fn update_user(
             set email = coalesce($1, "user".email),
                 username = coalesce($2, "user".username),
                 password_hash = coalesce($3, "user".password_hash),
-                bio = coalesce($4, "user".bio),
-                image = coalesce($5, "user".image)
-            where user_id = $6
-            returning email, username, bio, image
+                bio = case when $4 then coalesce($5, '') else "user".bio end,
+                image = case when $6 then $7 else "user".image end
+            where user_id = $8
+            returning email, username, bio, image, created_at, updated_at
         "#,
         req.user.email,
         req.user.username,
         password_hash,
-        req.user.bio,
-        req.user.image,
+        req.user.bio.is_some(),
+        req.user.bio.flatten(),
+        req.user.image.is_some(),
+        req.user.image.flatten(),
         claims.sub
     )
     .fetch_one(&state.db)
@@ -193,18 +516,168 @@ async fn update_user(
     Ok(Json(UserBody {
         user: User {
             email: user.email,
-            token: Claims::with_sub_to_jwt(claims.sub, &state),
+            token: Claims::with_sub_to_jwt(claims.sub, &state)?,
             username: user.username,
             bio: user.bio,
             image: user.image,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
         },
     }))
 }
 
-async fn hash_password(password: String) -> Result<String> {
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+const MIN_USERNAME_LENGTH: usize = 3;
+
+const MAX_USERNAME_LENGTH: usize = 40;
+
+/// Would be shadowed by the fixed routes under `/api/profiles`.
+const RESERVED_USERNAMES: &[&str] = &["search"];
+
+pub(super) fn validate_credentials(
+    email: Option<&str>,
+    password: Option<&str>,
+) -> Vec<(&'static str, Cow<'static, str>)> {
+    let mut errors = Vec::new();
+
+    if email.is_some_and(|email| !is_valid_email(email)) {
+        errors.push(("email", "is invalid".into()));
+    }
+    if password.is_some_and(|password| password.chars().count() < MIN_PASSWORD_LENGTH) {
+        errors.push((
+            "password",
+            format!("is too short (minimum is {MIN_PASSWORD_LENGTH} characters)").into(),
+        ));
+    }
+
+    errors
+}
+
+/// Expects `username` already trimmed. Lengths count characters, so non-Latin names are not
+/// penalized, and characters that would change the meaning of `/api/profiles/{username}` are
+/// refused.
+fn username_error(username: &str) -> Option<(&'static str, Cow<'static, str>)> {
+    let length = username.chars().count();
+
+    let message: Cow<_> = if length == 0 {
+        "can't be blank".into()
+    } else if length < MIN_USERNAME_LENGTH {
+        format!("is too short (minimum is {MIN_USERNAME_LENGTH} characters)").into()
+    } else if length > MAX_USERNAME_LENGTH {
+        format!("is too long (maximum is {MAX_USERNAME_LENGTH} characters)").into()
+    } else if username.chars().any(char::is_control) {
+        "can't contain control characters".into()
+    } else if username.contains(['/', '\\', '?', '#', '%']) {
+        "can't contain /, \\, ?, # or %".into()
+    } else if RESERVED_USERNAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(username))
+    {
+        "is reserved".into()
+    } else {
+        return None;
+    };
+
+    Some(("username", message))
+}
+
+/// Deliberately loose: one `@` with something on both sides and a dot inside the domain.
+/// Anything stricter is better left to a confirmation email.
+fn is_valid_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && !domain.contains('@')
+        && !email.chars().any(char::is_whitespace)
+        && domain
+            .split_once('.')
+            .is_some_and(|(name, _)| !name.is_empty() && !domain.ends_with('.'))
+}
+
+/// Only absolute `http`/`https` URLs are accepted so that an image can't smuggle in a
+/// `javascript:` or `data:` URI. An empty `allowed_hosts` accepts any host.
+fn validate_image_url(
+    image: &str,
+    allowed_hosts: &[String],
+) -> Option<(&'static str, Cow<'static, str>)> {
+    let Ok(url) = Url::parse(image) else {
+        return Some(("image", "is not a valid URL".into()));
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return Some(("image", "must be an http or https URL".into()));
+    }
+
+    let host = url.host_str().unwrap_or_default();
+    if !allowed_hosts.is_empty()
+        && !allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    {
+        return Some(("image", "is not hosted on an allowed host".into()));
+    }
+
+    None
+}
+
+/// Checks `username` and `email` against other accounts in one round trip so that a request
+/// taking both gets both reported. The unique constraints still back this up on a race.
+async fn taken_fields(
+    e: impl Executor<'_, Database = Postgres>,
+    username: Option<&str>,
+    email: Option<&str>,
+    user_id: Option<Uuid>,
+) -> Result<Vec<(&'static str, Cow<'static, str>)>> {
+    let taken = sqlx::query!(
+        // language=PostgreSQL
+        r#"
+            select
+                exists(select 1 from "user" where username = $1 and user_id is distinct from $3) "username!",
+                exists(select 1 from "user" where email = $2 and user_id is distinct from $3) "email!"
+        "#,
+        username,
+        email,
+        user_id
+    )
+    .fetch_one(e)
+    .await?;
+
+    let mut errors = Vec::new();
+    if taken.username {
+        errors.push(("username", "username taken".into()));
+    }
+    if taken.email {
+        errors.push(("email", "email taken".into()));
+    }
+
+    Ok(errors)
+}
+
+/// Builds the hasher used for new password hashes, rejecting cost parameters Argon2 does not accept.
+pub(crate) fn password_hasher(config: &Config) -> anyhow::Result<Argon2<'static>> {
+    let params = Params::new(
+        config.argon2_memory_cost_kib,
+        config.argon2_time_cost,
+        config.argon2_parallelism,
+        None,
+    )
+    .map_err(|e| anyhow::anyhow!("invalid Argon2 parameters: {e}"))?;
+
+    Ok(Argon2::new(
+        Algorithm::default(),
+        Version::default(),
+        params,
+    ))
+}
+
+pub(crate) async fn hash_password(argon2: &Argon2<'static>, password: String) -> Result<String> {
+    let argon2 = argon2.clone();
+
     tokio::task::spawn_blocking(move || {
         let salt = SaltString::generate(rand::thread_rng());
-        Ok(PasswordHash::generate(Argon2::default(), password, &salt)
+        Ok(PasswordHash::generate(argon2, password, &salt)
             .map_err(|e| anyhow::anyhow!("failed to generate password hash: {}", e))?
             .to_string())
     })
@@ -212,12 +685,56 @@ async fn hash_password(password: String) -> Result<String> {
     .context("panic in generating password hash")?
 }
 
-async fn verify_password(password: String, password_hash: String) -> Result<()> {
+/// Whether `password_hash` was produced with different Argon2 settings than new hashes get.
+fn needs_rehash(argon2: &Argon2, password_hash: &str) -> bool {
+    let Ok(hash) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+    let Ok(params) = Params::try_from(&hash) else {
+        return false;
+    };
+    let current = argon2.params();
+
+    hash.algorithm != Algorithm::default().ident()
+        || hash.version != Some(Version::default().into())
+        || params.m_cost() != current.m_cost()
+        || params.t_cost() != current.t_cost()
+        || params.p_cost() != current.p_cost()
+}
+
+async fn rehash_password(
+    e: impl Executor<'_, Database = Postgres>,
+    argon2: &Argon2<'static>,
+    user_id: Uuid,
+    password: String,
+) -> Result<()> {
+    let password_hash = hash_password(argon2, password).await?;
+
+    sqlx::query!(
+        // language=PostgreSQL
+        r#"update "user" set password_hash = $1 where user_id = $2"#,
+        password_hash,
+        user_id
+    )
+    .execute(e)
+    .await?;
+
+    Ok(())
+}
+
+/// Parameters are read from the stored hash, so hashes made under older settings keep verifying.
+async fn verify_password(
+    argon2: &Argon2<'static>,
+    password: String,
+    password_hash: String,
+) -> Result<()> {
+    let argon2 = argon2.clone();
+
     tokio::task::spawn_blocking(move || -> Result<()> {
         let hash = PasswordHash::new(&password_hash)
             .map_err(|e| anyhow::anyhow!("invalid password hash: {}", e))?;
 
-        hash.verify_password(&[&Argon2::default()], password)
+        hash.verify_password(&[&argon2], password)
             .map_err(|e| match e {
                 argon2::password_hash::Error::Password => Error::Unauthorized,
                 _ => anyhow::anyhow!("failed to verify password hash: {}", e).into(),
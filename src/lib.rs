@@ -1,2 +1,6 @@
 pub mod config;
 pub mod http;
+pub mod seed;
+
+/// Migrations embedded at build time, run on startup and checked by the readiness probe.
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!();
@@ -1,32 +1,67 @@
 use anyhow::Context;
 use clap::Parser;
-use conduit::{config::Config, http};
-use sqlx::postgres::PgPoolOptions;
+use conduit::config::{Command, Config, LogFormat};
+use conduit::{http, seed};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Connection};
+use std::str::FromStr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_env("RUST_LOG")
-                .unwrap_or(format!("{}=debug,tower_http=debug", env!("CARGO_CRATE_NAME")).into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
-
     let config = Config::parse();
 
+    let registry = tracing_subscriber::registry().with(
+        EnvFilter::try_from_env("RUST_LOG")
+            .unwrap_or(format!("{}=debug,tower_http=debug", env!("CARGO_CRATE_NAME")).into()),
+    );
+    match config.log_format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_span_list(false),
+            )
+            .init(),
+    }
+
+    let connect_options =
+        PgConnectOptions::from_str(&config.database_url).context("invalid DATABASE_URL")?;
+
+    // Migrations get a connection of their own, without the statement timeout, so that a long
+    // backfill or index build on a large database is not cancelled halfway through.
+    let mut migration_conn = connect_options
+        .connect()
+        .await
+        .context("failed to connect to database")?;
+    conduit::MIGRATOR.run(&mut migration_conn).await?;
+    migration_conn.close().await?;
+
+    let connect_options = connect_options.options([(
+        "statement_timeout",
+        format!("{}s", config.database_statement_timeout_seconds),
+    )]);
+
     let db = PgPoolOptions::new()
         .max_connections(50)
-        .connect(&config.database_url)
+        .connect_with(connect_options)
         .await
         .context("failed to connect to database")?;
 
-    sqlx::migrate!().run(&db).await?;
-
-    http::serve(config, db).await?;
+    match config.command {
+        Some(Command::Seed) => {
+            seed::run(&db, &config).await?;
+            tracing::info!(
+                "seeded demo data, every demo user's password is {}",
+                seed::PASSWORD
+            );
+        }
+        Some(Command::Serve) | None => http::serve(config, db, http::shutdown_signal()).await?,
+    }
 
     Ok(())
 }
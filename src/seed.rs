@@ -0,0 +1,256 @@
+use crate::config::Config;
+use crate::http::{hash_password, password_hasher, set_article_tags};
+use anyhow::Context;
+use sqlx::PgPool;
+
+/// Shared by every demo account.
+pub const PASSWORD: &str = "conduit-demo";
+
+const USERS: &[(&str, &str, &str)] = &[
+    (
+        "Miyuki",
+        "miyuki@conduit.demo",
+        "Knows a little about everything.",
+    ),
+    ("Minami", "minami@conduit.demo", "Quiet, but writes a lot."),
+    ("Yutaka", "yutaka@conduit.demo", ""),
+];
+
+struct Article {
+    author: &'static str,
+    slug: &'static str,
+    title: &'static str,
+    description: &'static str,
+    body: &'static str,
+    tags: &'static [&'static str],
+}
+
+const ARTICLES: &[Article] = &[
+    Article {
+        author: "Miyuki",
+        slug: "getting-started-with-axum",
+        title: "Getting started with axum",
+        description: "Routing, extractors and state in a few lines",
+        body: "axum builds on tower and hyper. Handlers are plain async functions whose arguments are extractors.",
+        tags: &["axum", "rust"],
+    },
+    Article {
+        author: "Miyuki",
+        slug: "compile-time-checked-queries",
+        title: "Compile-time checked queries",
+        description: "Letting sqlx catch typos before they ship",
+        body: "The query! macros check every statement against the database at build time.",
+        tags: &["rust", "sqlx"],
+    },
+    Article {
+        author: "Minami",
+        slug: "why-argon2",
+        title: "Why Argon2",
+        description: "Picking a password hash in 2025",
+        body: "Argon2id is memory-hard, which makes large-scale guessing on GPUs expensive.",
+        tags: &["security"],
+    },
+    Article {
+        author: "Yutaka",
+        slug: "my-first-article",
+        title: "My first article",
+        description: "Hello, Conduit",
+        body: "Nothing much to say yet, but it is nice to be here.",
+        tags: &[],
+    },
+];
+
+/// Article slug, author, body.
+const COMMENTS: &[(&str, &str, &str)] = &[
+    (
+        "getting-started-with-axum",
+        "Minami",
+        "The extractor section helped a lot.",
+    ),
+    ("getting-started-with-axum", "Yutaka", "Bookmarked!"),
+    (
+        "why-argon2",
+        "Miyuki",
+        "Worth mentioning the recommended parameters.",
+    ),
+];
+
+/// Follower, followed.
+const FOLLOWS: &[(&str, &str)] = &[
+    ("Minami", "Miyuki"),
+    ("Yutaka", "Miyuki"),
+    ("Yutaka", "Minami"),
+];
+
+/// User, article slug.
+const FAVORITES: &[(&str, &str)] = &[
+    ("Minami", "getting-started-with-axum"),
+    ("Yutaka", "getting-started-with-axum"),
+    ("Yutaka", "why-argon2"),
+];
+
+/// Inserts demo users, articles, comments, follows and favorites.
+///
+/// Rows that already exist are left alone, so running it again is a no-op.
+pub async fn run(db: &PgPool, config: &Config) -> anyhow::Result<()> {
+    let password_hash = hash_password(&password_hasher(config)?, PASSWORD.to_string()).await?;
+
+    let mut tx = db.begin().await?;
+
+    for (username, email, bio) in USERS {
+        sqlx::query!(
+            // language=PostgreSQL
+            r#"
+                insert into "user" (username, email, bio, password_hash)
+                values ($1, $2, $3, $4)
+                on conflict do nothing
+            "#,
+            username,
+            email,
+            bio,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("failed to seed user {username}"))?;
+    }
+
+    for article in ARTICLES {
+        let tags: Vec<String> = article.tags.iter().map(|tag| tag.to_string()).collect();
+
+        let article_id = sqlx::query_scalar!(
+            // language=PostgreSQL
+            r#"
+                insert into article (user_id, slug, title, description, body)
+                select user_id, $2, $3, $4, $5
+                from "user" where username = $1
+                on conflict (slug) do nothing
+                returning article_id
+            "#,
+            article.author,
+            article.slug,
+            article.title,
+            article.description,
+            article.body
+        )
+        .fetch_optional(&mut *tx)
+        .await
+        .with_context(|| format!("failed to seed article {}", article.slug))?;
+
+        if let Some(article_id) = article_id {
+            set_article_tags(&mut *tx, article_id, &tags)
+                .await
+                .with_context(|| format!("failed to seed tags of {}", article.slug))?;
+        }
+    }
+
+    for (slug, author, body) in COMMENTS {
+        sqlx::query!(
+            // language=PostgreSQL
+            r#"
+                insert into article_comment (article_id, user_id, body)
+                select article_id, "user".user_id, $3
+                from article, "user"
+                where slug = $1 and username = $2
+                and not exists(
+                    select 1 from article_comment comment
+                    where comment.article_id = article.article_id
+                    and comment.user_id = "user".user_id
+                    and comment.body = $3
+                )
+            "#,
+            slug,
+            author,
+            body
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("failed to seed comment on {slug}"))?;
+    }
+
+    for (follower, followed) in FOLLOWS {
+        sqlx::query!(
+            // language=PostgreSQL
+            r#"
+                insert into follow (following_user_id, followed_user_id)
+                select follower.user_id, followed.user_id
+                from "user" follower, "user" followed
+                where follower.username = $1 and followed.username = $2
+                on conflict do nothing
+            "#,
+            follower,
+            followed
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("failed to seed follow {follower} -> {followed}"))?;
+    }
+
+    for (username, slug) in FAVORITES {
+        sqlx::query!(
+            // language=PostgreSQL
+            r#"
+                insert into article_favorite (article_id, user_id)
+                select article_id, "user".user_id
+                from article, "user"
+                where slug = $2 and username = $1
+                on conflict do nothing
+            "#,
+            username,
+            slug
+        )
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("failed to seed favorite {username} -> {slug}"))?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    async fn counts(db: &PgPool) -> [i64; 5] {
+        let mut counts = [0; 5];
+        for (count, table) in counts.iter_mut().zip([
+            r#""user""#,
+            "article",
+            "article_comment",
+            "follow",
+            "article_favorite",
+        ]) {
+            *count = sqlx::query_scalar(&format!("select count(*) from {table}"))
+                .fetch_one(db)
+                .await
+                .unwrap();
+        }
+        counts
+    }
+
+    #[sqlx::test(migrator = "crate::MIGRATOR")]
+    async fn running_twice_changes_nothing(db: PgPool) {
+        let config =
+            Config::try_parse_from(["conduit", "--database-url", "postgres://localhost/conduit"])
+                .unwrap();
+
+        run(&db, &config).await.unwrap();
+        let first = counts(&db).await;
+        run(&db, &config).await.unwrap();
+
+        assert_eq!(first, counts(&db).await);
+        assert_eq!(
+            first,
+            [
+                USERS.len() as i64,
+                ARTICLES.len() as i64,
+                COMMENTS.len() as i64,
+                FOLLOWS.len() as i64,
+                FAVORITES.len() as i64,
+            ]
+        );
+    }
+}